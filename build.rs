@@ -0,0 +1,9 @@
+fn main() {
+    // protoc comes with the build, it does not have to be installed.
+    std::env::set_var(
+        "PROTOC",
+        protoc_bin_vendored::protoc_bin_path().expect("No protoc for this platform"),
+    );
+    tonic_prost_build::compile_protos("proto/wbl.proto")
+        .expect("Could not compile proto/wbl.proto");
+}
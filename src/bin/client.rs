@@ -1,34 +1,285 @@
-use std::{
-    io::{Read, Write},
-    net::TcpStream
+use std::{fs::File, io, process::ExitCode};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use tokio::{
+    io::{split, AsyncRead, AsyncWrite, BufReader},
+    net::TcpStream,
+};
+use wbl::{
+    planes::Input,
+    protocol::{read_message, write_message, Request, RequestBody, Response, ResponseBody},
+    stations::parse_station_flags,
+    tls::{connector, server_name},
 };
 
-fn main() {
-    let json_str = r#"
-    {
-    "name": "MOA",
-    "values": {
-        "base": 453.5,
-        "fuel": 85.0,
-        "trip_fuel": 35.0,
-        "bagage_back": 0.0,
-        "bagage_front": 1.0,
-        "bagage_wings": 2.0,
-        "pilot": 70.0,
-        "co_pilot": 0.0
-    }
-}
-    "#;
-
-    if let Ok(mut stream) = TcpStream::connect("127.0.0.1:8080") {
-        println!("created stream");
-        let result = stream.write(json_str.as_bytes());
-        println!("wrote to stream; success={:?}", result.is_ok());
-        let mut buf: [u8; 50] = [0; 50];
-        let n = stream.read(&mut buf).expect("Read must work lol");
-        println!("Got n bytes: {}", n);
-        println!("{:?}", String::from_utf8(buf[0..n].to_vec()));
+/// Exit status when the loading passed W&B.
+const APPROVED: u8 = 0;
+/// Exit status when the loading failed W&B or no seating passed.
+const FAILED: u8 = 1;
+/// Exit status when the request could not be made or handled.
+const ERROR: u8 = 2;
+
+/// Sends a loading to the W&B server. Exits with 0 when the loading passed
+/// W&B, 1 when it failed and 2 on errors.
+///
+/// Station flags like `--pilot 82 --fuel 60L` can be given anywhere among
+/// the options and override the input file.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Server address
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+    /// Input file with the loading, `-` reads it from stdin
+    #[arg(short, long)]
+    path: Option<String>,
+    /// Connect with TLS, trusting the certificates in this PEM file
+    #[arg(long)]
+    ca: Option<String>,
+    /// Token the server requires
+    #[arg(long)]
+    token: Option<String>,
+    /// Print the response as JSON
+    #[arg(long)]
+    json: bool,
+    /// Number of decimals in the output
+    #[arg(long, default_value_t = 1)]
+    precision: usize,
+    /// Plane, overrides the name in the input file
+    plane: Option<String>,
+    /// Station flags, split off by `parse_args`.
+    #[arg(skip)]
+    stations: Vec<String>,
+}
+
+/// Parses the command line, long flags that are not options of the client
+/// are station flags and take the next argument as value unless given with
+/// `=`.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Args {
+    let command = Args::command();
+    let mut own = Vec::new();
+    let mut stations = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--").filter(|flag| !flag.is_empty()) else {
+            own.push(arg);
+            continue;
+        };
+        let name = flag.split('=').next().unwrap_or(flag);
+        let option = command
+            .get_arguments()
+            .find(|option| option.get_long() == Some(name));
+        let (target, takes_value) = match option {
+            Some(option) => (&mut own, option.get_action().takes_values()),
+            None if name == "help" || name == "version" => (&mut own, false),
+            None => (&mut stations, true),
+        };
+        let has_value = flag.contains('=');
+        target.push(arg);
+        if takes_value && !has_value {
+            target.extend(args.next());
+        }
+    }
+    let mut parsed = Args::parse_from(own);
+    parsed.stations = stations;
+    parsed
+}
+
+fn read_input(args: &Args) -> Result<Input, String> {
+    let mut input = match &args.path {
+        Some(path) if path == "-" => serde_json::from_reader(io::stdin().lock())
+            .map_err(|e| format!("Invalid format of input: {}", e))?,
+        Some(path) => {
+            let file = File::open(path).map_err(|e| format!("Input file {}: {}", path, e))?;
+            serde_json::from_reader(io::BufReader::new(file))
+                .map_err(|e| format!("Invalid format of input file: {}", e))?
+        }
+        None if args.plane.is_some() => Input::default(),
+        None => return Err("Either --path or a plane is required".to_string()),
+    };
+    if let Some(plane) = &args.plane {
+        input.name = plane.clone();
+    }
+    let stations = parse_station_flags(&input.name, &args.stations)?;
+    input.values.extend(stations.values);
+    Ok(input)
+}
+
+async fn send(args: &Args, request: &Request) -> io::Result<Response> {
+    let socket = TcpStream::connect(&args.address).await?;
+    match &args.ca {
+        Some(ca) => {
+            let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+            let connector = connector(ca).map_err(invalid)?;
+            let name = server_name(&args.address).map_err(invalid)?;
+            exchange(connector.connect(name, socket).await?, request).await
+        }
+        None => exchange(socket, request).await,
+    }
+}
+
+async fn exchange(stream: impl AsyncRead + AsyncWrite, request: &Request) -> io::Result<Response> {
+    let (reader, mut writer) = split(stream);
+    write_message(&mut writer, request).await?;
+    match read_message::<Response>(&mut BufReader::new(reader)).await? {
+        Some(response) => response.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Server closed the connection",
+        )),
+    }
+}
+
+fn exit_status(body: &ResponseBody) -> u8 {
+    match body {
+        ResponseBody::Outcome { outcome }
+            if outcome.take_off.is_none() && outcome.landing.is_none() =>
+        {
+            APPROVED
+        }
+        ResponseBody::Seats { seats } if !seats.is_empty() => APPROVED,
+        ResponseBody::Outcome { .. } | ResponseBody::Seats { .. } => FAILED,
+        ResponseBody::Error { .. } => ERROR,
+    }
+}
+
+fn print_response(name: &str, body: &ResponseBody, p: usize) {
+    match body {
+        ResponseBody::Outcome { outcome } => {
+            match &outcome.take_off {
+                Some(reason) => println!("Plane: {} failed W&B for: {:?}", name, reason),
+                None => println!("Plane: {} is approved for W&B", name),
+            }
+            println!("Plane: {} has W&B point at: {:.p$}", name, outcome.point);
+            match &outcome.landing {
+                Some(reason) => println!("Plane: {} failed landing W&B for: {:?}", name, reason),
+                None => println!("Plane: {} is approved for W&B when landing", name),
+            }
+            println!(
+                "Plane: {} has a landing W&B point at: {:.p$}",
+                name, outcome.landing_point
+            );
+        }
+        ResponseBody::Seats { seats } => {
+            if seats.is_empty() {
+                println!("Plane: {} has no seating that passes W&B", name);
+            }
+            for assignment in seats {
+                println!(
+                    "Plane: {} seating {:?} has margin {:.p$}",
+                    name, assignment.seats, assignment.margin
+                );
+            }
+        }
+        ResponseBody::Error { message } => eprintln!("Request failed: {}", message),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = parse_args(std::env::args());
+    let input = read_input(&args)
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit());
+    let name = input.name.clone();
+    let mut request = if input.people.is_empty() {
+        Request::new(RequestBody::Calculate { input })
     } else {
-        println!("failed lol");
+        Request::new(RequestBody::AssignSeats { input })
+    };
+    request.token = args.token.clone();
+
+    let response = match send(&args, &request).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Request to {} failed: {}", args.address, e);
+            return ExitCode::from(ERROR);
+        }
+    };
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&response).expect("Response is serializable")
+        );
+    } else {
+        print_response(&name, &response.body, args.precision);
+    }
+    ExitCode::from(exit_status(&response.body))
+}
+
+#[cfg(test)]
+mod tests {
+    use wbl::{history::Outcome, FailReason, WeightLever};
+
+    use super::*;
+
+    fn outcome(landing: Option<FailReason>) -> ResponseBody {
+        ResponseBody::Outcome {
+            outcome: Outcome {
+                take_off: None,
+                landing,
+                point: WeightLever::default(),
+                landing_point: WeightLever::default(),
+                revision: None,
+            },
+        }
+    }
+
+    #[test]
+    fn exit_status_of_response() {
+        assert_eq!(exit_status(&outcome(None)), APPROVED);
+        assert_eq!(
+            exit_status(&outcome(Some(FailReason::MaxLandingWeight))),
+            FAILED
+        );
+        assert_eq!(
+            exit_status(&ResponseBody::Seats { seats: Vec::new() }),
+            FAILED
+        );
+        assert_eq!(
+            exit_status(&ResponseBody::Error {
+                message: "Server busy".to_string()
+            }),
+            ERROR
+        );
+    }
+
+    #[test]
+    fn stations_override_plane() {
+        let args = parse_args(["client", "SEMOA", "--pilot", "82", "--fuel=60L"].map(String::from));
+        let input = read_input(&args).unwrap();
+
+        assert_eq!(input.name, "SEMOA");
+        assert_eq!(input.values["pilot"].as_f64(), Some(82.0));
+        assert_eq!(input.values["fuel"].as_str(), Some("60L"));
+    }
+
+    #[test]
+    fn stations_with_input_file() {
+        let args =
+            parse_args(["client", "--path", "input.json", "--pilot", "82"].map(String::from));
+
+        assert_eq!(args.path.as_deref(), Some("input.json"));
+        assert_eq!(args.plane, None);
+        assert_eq!(args.stations, ["--pilot", "82"]);
+    }
+
+    #[test]
+    fn options_after_stations() {
+        let args = parse_args(
+            [
+                "client",
+                "SEMOA",
+                "--pilot",
+                "-82",
+                "--address",
+                "wbl:8080",
+                "--json",
+            ]
+            .map(String::from),
+        );
+
+        assert_eq!(args.plane.as_deref(), Some("SEMOA"));
+        assert_eq!(args.address, "wbl:8080");
+        assert!(args.json);
+        assert_eq!(args.stations, ["--pilot", "-82"]);
     }
 }
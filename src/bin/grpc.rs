@@ -0,0 +1,475 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use clap::Parser;
+use log::{info, warn};
+use serde_json::Value;
+use tonic::{transport::Server, Request, Response, Status};
+use wbl::{
+    parse_plane_config,
+    planes::{EnvelopeAxis, Input, LateralAxis, PlaneData, DEFAULT_FUEL_DENSITY},
+    service::{blocking, calc, calc_max_fuel, shutdown_signal, Lookups},
+    stations::station_key,
+    FailReason, WeightLever,
+};
+
+mod proto {
+    tonic::include_proto!("wbl.v1");
+}
+
+use proto::{
+    weight_and_balance_server::{WeightAndBalance, WeightAndBalanceServer},
+    Aircraft, AircraftSummary, Check, ComputeResponse, GetAircraftRequest, ListAircraftRequest,
+    ListAircraftResponse, Loading, Point, SolveMaxFuelResponse,
+};
+
+/// Serves the W&B calculations over gRPC, see proto/wbl.proto.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    address: SocketAddr,
+    /// Plane config
+    #[arg(long, default_value = "./src/input/config.json")]
+    config: String,
+}
+
+struct Service {
+    planes: Arc<Vec<PlaneData>>,
+}
+
+impl Service {
+    fn plane(&self, name: &str) -> Result<&PlaneData, Status> {
+        self.planes
+            .iter()
+            .find(|plane| plane.name == name)
+            .ok_or_else(|| Status::not_found(format!("Plane missing in config: {}", name)))
+    }
+}
+
+/// The loading as an input, a station may only be given in kilograms or in
+/// litres.
+fn input(loading: Loading) -> Result<Input, String> {
+    if let Some(station) = loading
+        .litres
+        .keys()
+        .find(|station| loading.kilograms.contains_key(*station))
+    {
+        return Err(format!("Station {} given in kilograms and litres", station));
+    }
+    let mut values: HashMap<String, Value> = loading
+        .kilograms
+        .into_iter()
+        .map(|(station, weight)| (station, weight.into()))
+        .collect();
+    values.extend(
+        loading
+            .litres
+            .into_iter()
+            .map(|(station, litres)| (station, format!("{}L", litres).into())),
+    );
+    Ok(Input {
+        name: loading.aircraft,
+        values,
+        footprints: loading.footprints,
+        ..Default::default()
+    })
+}
+
+fn invalid(method: &str, e: String) -> Status {
+    warn!(method = method; "Request failed: {}", e);
+    Status::invalid_argument(e)
+}
+
+fn point(point: WeightLever) -> Option<Point> {
+    Some(Point {
+        weight: point.weight,
+        lever: point.lever,
+    })
+}
+
+/// The proto variant named like the reason, e.g. `FAIL_REASON_MAX_TAKE_OFF_WEIGHT`
+/// for `MaxTakeOffWeight`.
+fn fail_reason(reason: &FailReason) -> Result<proto::FailReason, Status> {
+    let mut name = "FAIL_REASON".to_string();
+    for c in reason.name().chars() {
+        if c.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    proto::FailReason::from_str_name(&name)
+        .ok_or_else(|| Status::internal(format!("{} missing in the proto", name)))
+}
+
+fn check(reason: Option<FailReason>, at: WeightLever) -> Result<Option<Check>, Status> {
+    let detail = match &reason {
+        Some(FailReason::CombinedLimit(names)) => names.join(", "),
+        Some(FailReason::MinWeight(kind) | FailReason::FloorLoad(kind)) => station_key(*kind),
+        _ => String::new(),
+    };
+    let reason = match &reason {
+        Some(reason) => fail_reason(reason)?,
+        None => proto::FailReason::Unspecified,
+    };
+    Ok(Some(Check {
+        fail_reason: reason.into(),
+        detail,
+        point: point(at),
+    }))
+}
+
+/// The proto axis and its moment divisor.
+fn lever_axis(axis: EnvelopeAxis) -> (proto::LeverAxis, Option<f64>) {
+    match axis {
+        EnvelopeAxis::Lever => (proto::LeverAxis::Lever, None),
+        EnvelopeAxis::Moment(divisor) => (proto::LeverAxis::Moment, Some(divisor)),
+        EnvelopeAxis::PercentMac => (proto::LeverAxis::PercentMac, None),
+    }
+}
+
+fn corners(vertices: &[[f64; 2]; 6]) -> Vec<Point> {
+    vertices
+        .iter()
+        .map(|[weight, lever]| Point {
+            weight: *weight,
+            lever: *lever,
+        })
+        .collect()
+}
+
+fn aircraft(plane: &PlaneData) -> Aircraft {
+    let (envelope_axis, moment_divisor) = lever_axis(plane.envelope_axis);
+    Aircraft {
+        name: plane.name.clone(),
+        revision: plane.revision.clone(),
+        effective_date: plane.effective_date.clone(),
+        config_hash: plane.content_hash(),
+        max_take_off_weight: plane.max_weights.max_take_off_weight,
+        max_landing_weight: plane.max_weights.max_landing_weight,
+        max_fuel_weight: plane.max_weights.max_fuel_weight,
+        fuel_density: plane.fuel_density.unwrap_or(DEFAULT_FUEL_DENSITY),
+        levers: plane
+            .to_lever_map()
+            .into_iter()
+            .map(|(kind, lever)| (station_key(kind), lever))
+            .collect(),
+        default_loading: plane
+            .default_loading
+            .iter()
+            .map(|(kind, weight)| (station_key(*kind), *weight))
+            .collect(),
+        envelope: corners(&plane.vertices),
+        envelope_axis: envelope_axis.into(),
+        moment_divisor,
+        mac: plane.mac.as_ref().map(|mac| proto::Mac {
+            leading_edge: mac.leading_edge,
+            length: mac.length,
+        }),
+        lateral_envelope: plane.lateral_envelope.as_ref().map(|envelope| {
+            let (lever_axis, moment_divisor) = lever_axis(envelope.lever_axis);
+            proto::LateralEnvelope {
+                axis: match envelope.axis {
+                    LateralAxis::Weight => proto::LateralAxis::Weight,
+                    LateralAxis::Longitudinal => proto::LateralAxis::Longitudinal,
+                }
+                .into(),
+                lever_axis: lever_axis.into(),
+                moment_divisor,
+                corners: corners(&envelope.vertices),
+            }
+        }),
+    }
+}
+
+#[tonic::async_trait]
+impl WeightAndBalance for Service {
+    async fn compute(
+        &self,
+        request: Request<Loading>,
+    ) -> Result<Response<ComputeResponse>, Status> {
+        let loading = request.into_inner();
+        let name = loading.aircraft.clone();
+        let planes = self.planes.clone();
+        let outcome = blocking(move || calc(input(loading)?, &planes, &Lookups::default()))
+            .await
+            .map_err(|e| invalid("compute", e))?;
+        info!(method = "compute", plane = name; "Request handled");
+        Ok(Response::new(ComputeResponse {
+            take_off: check(outcome.take_off, outcome.point)?,
+            landing: check(outcome.landing, outcome.landing_point)?,
+            revision: outcome.revision,
+        }))
+    }
+
+    async fn list_aircraft(
+        &self,
+        _: Request<ListAircraftRequest>,
+    ) -> Result<Response<ListAircraftResponse>, Status> {
+        let aircraft = self
+            .planes
+            .iter()
+            .map(|plane| AircraftSummary {
+                name: plane.name.clone(),
+                revision: plane.revision.clone(),
+            })
+            .collect();
+        Ok(Response::new(ListAircraftResponse { aircraft }))
+    }
+
+    async fn get_aircraft(
+        &self,
+        request: Request<GetAircraftRequest>,
+    ) -> Result<Response<Aircraft>, Status> {
+        let plane = self.plane(&request.get_ref().name)?;
+        Ok(Response::new(aircraft(plane)))
+    }
+
+    async fn solve_max_fuel(
+        &self,
+        request: Request<Loading>,
+    ) -> Result<Response<SolveMaxFuelResponse>, Status> {
+        let loading = request.into_inner();
+        let density = self
+            .plane(&loading.aircraft)?
+            .fuel_density
+            .unwrap_or(DEFAULT_FUEL_DENSITY);
+        let name = loading.aircraft.clone();
+        let planes = self.planes.clone();
+        let kilograms =
+            blocking(move || calc_max_fuel(input(loading)?, &planes, &Lookups::default()))
+                .await
+                .map_err(|e| invalid("solve_max_fuel", e))?;
+        info!(method = "solve_max_fuel", plane = name; "Request handled");
+        Ok(Response::new(SolveMaxFuelResponse {
+            kilograms,
+            litres: kilograms.map(|kilograms| kilograms / density),
+        }))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), tonic::transport::Error> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let contents = std::fs::read_to_string(&args.config).expect("Config not found");
+    let planes = parse_plane_config(&contents).unwrap_or_else(|e| panic!("{}", e));
+    let service = Service {
+        planes: Arc::new(planes),
+    };
+
+    info!("Serving gRPC on {}", args.address);
+    Server::builder()
+        .add_service(WeightAndBalanceServer::new(service))
+        .serve_with_shutdown(args.address, shutdown_signal())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+    use tonic::{
+        transport::{server::TcpIncoming, Channel},
+        Code,
+    };
+
+    use super::*;
+    use proto::weight_and_balance_client::WeightAndBalanceClient;
+    use wbl::{planes::Mac, Kind};
+
+    async fn start() -> (
+        WeightAndBalanceClient<Channel>,
+        oneshot::Sender<()>,
+        JoinHandle<()>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let planes = parse_plane_config(include_str!("../input/config.json")).unwrap();
+        let service = Service {
+            planes: Arc::new(planes),
+        };
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            Server::builder()
+                .add_service(WeightAndBalanceServer::new(service))
+                .serve_with_incoming_shutdown(TcpIncoming::from(listener), async {
+                    let _ = stopped.await;
+                })
+                .await
+                .unwrap();
+        });
+        let client = WeightAndBalanceClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        (client, stop, server)
+    }
+
+    fn loading(kilograms: &[(&str, f64)], litres: &[(&str, f64)]) -> Loading {
+        let map = |values: &[(&str, f64)]| {
+            values
+                .iter()
+                .map(|(station, value)| (station.to_string(), *value))
+                .collect()
+        };
+        Loading {
+            aircraft: "SEMOA".to_string(),
+            kilograms: map(kilograms),
+            litres: map(litres),
+            footprints: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn aircraft_and_compute() {
+        let (mut client, stop, server) = start().await;
+
+        let list = client
+            .list_aircraft(ListAircraftRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(list.aircraft.iter().any(|plane| plane.name == "SEMOA"));
+        let plane = client
+            .get_aircraft(GetAircraftRequest {
+                name: "SEMOA".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(plane.levers.contains_key("pilot"));
+        assert_eq!(plane.envelope.len(), 6);
+        assert_eq!(plane.envelope_axis(), proto::LeverAxis::Lever);
+        assert_eq!(plane.moment_divisor, None);
+        let missing = client
+            .get_aircraft(GetAircraftRequest {
+                name: "NOPLANE".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+
+        let outcome = client
+            .compute(loading(&[("pilot", 82.0)], &[("fuel", 60.0)]))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(outcome.revision, plane.revision);
+        let take_off = outcome.take_off.unwrap();
+        assert!(take_off.point.unwrap().weight > 500.0);
+        let invalid = client
+            .compute(Loading {
+                aircraft: "NOPLANE".to_string(),
+                ..loading(&[], &[])
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(invalid.code(), Code::InvalidArgument);
+        let twice = client
+            .compute(loading(
+                &[("pilot", 82.0), ("fuel", 40.0)],
+                &[("fuel", 60.0)],
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(twice.code(), Code::InvalidArgument);
+        assert_eq!(
+            twice.message(),
+            "Station fuel given in kilograms and litres"
+        );
+
+        drop(client);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn every_fail_reason_in_proto() {
+        let reasons = [
+            FailReason::Bagage,
+            FailReason::BagageFront,
+            FailReason::BagageBack,
+            FailReason::BagageWings,
+            FailReason::MaxTakeOffWeight,
+            FailReason::MaxWingLoad,
+            FailReason::Fuel,
+            FailReason::ZeroFuel,
+            FailReason::LandingFuel,
+            FailReason::MaxLandingWeight,
+            FailReason::CombinedLimit(Vec::new()),
+            FailReason::MinWeight(Kind::Pilot),
+            FailReason::FloorLoad(Kind::Bagage),
+            FailReason::TorqueOutOfBounds,
+            FailReason::LateralOutOfBounds,
+            FailReason::MinCockpitLoad,
+            FailReason::MaxCockpitLoad,
+            FailReason::WaterBallast,
+            FailReason::TailBallast,
+        ];
+
+        for (reason, number) in reasons.iter().zip(1..) {
+            assert_eq!(fail_reason(reason).unwrap() as i32, number, "{:?}", reason);
+        }
+    }
+
+    #[test]
+    fn aircraft_describes_envelope_axes() {
+        let plane = PlaneData {
+            envelope_axis: EnvelopeAxis::Moment(1000.0),
+            mac: Some(Mac {
+                leading_edge: 1.8,
+                length: 1.4,
+            }),
+            lateral_envelope: Some(wbl::planes::LateralEnvelope {
+                axis: LateralAxis::Longitudinal,
+                lever_axis: EnvelopeAxis::Moment(100.0),
+                vertices: [[2.0, -5.0]; 6],
+            }),
+            ..Default::default()
+        };
+
+        let aircraft = aircraft(&plane);
+        assert_eq!(aircraft.envelope_axis(), proto::LeverAxis::Moment);
+        assert_eq!(aircraft.moment_divisor, Some(1000.0));
+        assert_eq!(aircraft.mac.unwrap().length, 1.4);
+        let lateral = aircraft.lateral_envelope.unwrap();
+        assert_eq!(lateral.axis(), proto::LateralAxis::Longitudinal);
+        assert_eq!(lateral.lever_axis(), proto::LeverAxis::Moment);
+        assert_eq!(lateral.moment_divisor, Some(100.0));
+        assert_eq!(lateral.corners[0].lever, -5.0);
+    }
+
+    #[tokio::test]
+    async fn max_fuel_passes() {
+        let (mut client, stop, server) = start().await;
+
+        let max_fuel = client
+            .solve_max_fuel(loading(&[("pilot", 82.0)], &[]))
+            .await
+            .unwrap()
+            .into_inner();
+        let kilograms = max_fuel.kilograms.unwrap();
+        assert!(max_fuel.litres.unwrap() > kilograms);
+        let fueled = |fuel| loading(&[("pilot", 82.0), ("fuel", fuel), ("trip_fuel", 0.0)], &[]);
+        let outcome = client
+            .compute(fueled(kilograms))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            outcome.take_off.unwrap().fail_reason(),
+            proto::FailReason::Unspecified
+        );
+        let outcome = client
+            .compute(fueled(kilograms + 0.5))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_ne!(
+            outcome.take_off.unwrap().fail_reason(),
+            proto::FailReason::Unspecified
+        );
+
+        drop(client);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
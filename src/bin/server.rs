@@ -1,93 +1,1191 @@
-use std::{collections::HashMap, io};
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use log::error;
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{split, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc, oneshot, watch, OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+    time::timeout,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{
+            ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+        },
+        http::StatusCode,
+        Message,
+    },
+    WebSocketStream,
 };
 use wbl::{
-    calc_wb::WeightAndBalance,
-    iterate_maps, parse_name_from_input, parse_values_from_input,
-    planes::{Input, ParsedInput, PlaneData, PlaneProperties},
-    read_plane_config_from_json, FailReason, WeightLever,
+    config_hash,
+    metrics::Metrics,
+    parse_plane_config,
+    planes::PlaneData,
+    protocol::{
+        read_message, write_message, MessageTooLong, Request, RequestBody, Response, ResponseBody,
+    },
+    service::{blocking, calc, calc_partial, calc_seats, shutdown_signal, Lookups},
+    session::{Session, SessionRequest, SessionUpdate},
+    tls::TlsFiles,
 };
 
-fn calc(input: Input) -> Result<(), FailReason> {
-    println!("Got data: {:?}", input);
-    let planes = read_plane_config_from_json("./src/input/config.json");
+#[derive(Parser, Debug, Clone)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+    /// Seconds a connection may be idle before it is closed
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+    /// Connections handled at the same time, more are turned away
+    #[arg(long, default_value_t = 64)]
+    max_connections: usize,
+    /// Plane config, reloaded when it changes
+    #[arg(long, default_value = "./src/input/config.json")]
+    config: String,
+    /// Saved pilot and passenger profiles, read at startup
+    #[arg(long, default_value = "./src/input/profiles.json")]
+    profiles: String,
+    /// Custom passenger mass tables, read at startup
+    #[arg(long, default_value = "./src/input/mass_tables.json")]
+    mass_tables: String,
+    /// Seconds between checks of the config for changes
+    #[arg(long, default_value_t = 2)]
+    reload_interval: u64,
+    /// Server config with TLS and tokens, see `ServerConfig`
+    #[arg(long)]
+    server_config: Option<String>,
+    /// Address to listen on for WebSocket planning sessions, none when not set
+    #[arg(long)]
+    session_address: Option<String>,
+    /// Address to serve `GET /metrics` on, none when not set
+    #[arg(long)]
+    metrics_address: Option<String>,
+}
+
+/// Settings read from `--server-config`, a json file like
+/// `{"tls": {"cert": "cert.pem", "key": "key.pem"}, "tokens": ["secret"]}`.
+#[derive(Deserialize, Debug, Default)]
+struct ServerConfig {
+    /// Only TLS connections are accepted when set.
+    tls: Option<TlsFiles>,
+    /// When not empty, every request must carry one of these tokens.
+    #[serde(default)]
+    tokens: Vec<String>,
+}
+
+fn read_server_config(path: &str) -> ServerConfig {
+    let file = std::fs::File::open(path).expect("Server config not found");
+    serde_json::from_reader(std::io::BufReader::new(file)).expect("Invalid format of server config")
+}
+
+/// What the connections share with the server.
+#[derive(Clone)]
+struct Shared {
+    idle: Duration,
+    config: watch::Receiver<Planes>,
+    lookups: Arc<Lookups>,
+    stopping: watch::Receiver<bool>,
+    tokens: Arc<Vec<String>>,
+    sessions: Sessions,
+    metrics: Arc<Metrics>,
+    request_ids: Arc<AtomicU64>,
+}
+
+impl Shared {
+    /// Id of a request or session change, to find its lines in the log.
+    fn next_request_id(&self) -> u64 {
+        self.request_ids.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// The current config, swapped as a whole when it is reloaded.
+type Planes = Arc<Vec<PlaneData>>;
+
+/// Open planning sessions by id.
+type Sessions = Arc<Mutex<HashMap<String, SessionHandle>>>;
+
+/// A session's task takes the changes, its participants get the updates.
+#[derive(Clone)]
+struct SessionHandle {
+    changes: mpsc::Sender<Change>,
+    updates: broadcast::Sender<String>,
+}
+
+/// What a participant's connection tells the session's task.
+enum Change {
+    /// Someone joined or left, the state is sent again with the new count.
+    Participants,
+    Request(SessionRequest, oneshot::Sender<Result<(), String>>),
+}
+
+/// Which listener a connection came in on.
+#[derive(Clone, Copy)]
+enum Endpoint {
+    Requests,
+    Sessions,
+    Metrics,
+}
+
+/// The server's listeners, sessions and metrics are optional.
+struct Listeners {
+    requests: TcpListener,
+    sessions: Option<TcpListener>,
+    metrics: Option<TcpListener>,
+}
+
+fn respond(
+    request: Request,
+    planes: &[PlaneData],
+    lookups: &Lookups,
+) -> Result<ResponseBody, String> {
+    Ok(match request.body {
+        RequestBody::Calculate { input } => ResponseBody::Outcome {
+            outcome: calc(input, planes, lookups)?,
+        },
+        RequestBody::AssignSeats { input } => ResponseBody::Seats {
+            seats: calc_seats(input, planes, lookups)?,
+        },
+    })
+}
 
-    let parsed_input = ParsedInput {
-        name: parse_name_from_input(&input),
-        values: parse_values_from_input(&input),
+/// Compares all bytes, so the time taken does not tell how much of a token
+/// was right.
+fn is_token(token: &str, tokens: &[String]) -> bool {
+    tokens.iter().fold(false, |found, known| {
+        let same = known.len() == token.len()
+            && known
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        found | same
+    })
+}
+
+fn is_authorized(token: Option<&str>, tokens: &[String]) -> bool {
+    tokens.is_empty() || token.is_some_and(|token| is_token(token, tokens))
+}
+
+fn authorize(request: &Request, tokens: &[String]) -> Result<(), String> {
+    match is_authorized(request.token.as_deref(), tokens) {
+        true => Ok(()),
+        false => Err("Unauthorized".to_string()),
+    }
+}
+
+async fn handle(request: Result<Request, String>, shared: &Shared, peer: SocketAddr) -> Response {
+    let id = shared.next_request_id();
+    let started = Instant::now();
+    let (kind, plane) = match &request {
+        Ok(request) => (request.body.kind(), request.body.input().name.clone()),
+        Err(_) => ("invalid", String::new()),
+    };
+    let planes = shared.config.borrow().clone();
+    let lookups = shared.lookups.clone();
+    let request = request.and_then(|request| authorize(&request, &shared.tokens).map(|_| request));
+    let body = match request {
+        Ok(request) => blocking(move || respond(request, &planes, &lookups)).await,
+        Err(e) => Err(e),
     };
+    let elapsed = started.elapsed();
+    let micros = elapsed.as_micros() as u64;
+    shared.metrics.request(kind, body.is_ok(), elapsed);
+    match &body {
+        Ok(body) => {
+            if let ResponseBody::Outcome { outcome } = body {
+                shared.metrics.outcome(&plane, outcome);
+            }
+            info!(
+                request_id = id, peer:% = peer, kind = kind, plane = plane, micros = micros;
+                "Request handled"
+            );
+        }
+        Err(e) => {
+            warn!(
+                request_id = id, peer:% = peer, kind = kind, plane = plane, micros = micros;
+                "Request failed: {}", e
+            );
+        }
+    }
+    body.map(Response::new).unwrap_or_else(Response::error)
+}
 
-    let plane_config: &PlaneData = &planes[planes
-        .iter()
-        .position(|plane| plane.name == parsed_input.name)
-        .expect("Plane missing in config")];
-    let plane_levers = plane_config.to_lever_map();
-    let plane_properties =
-        PlaneProperties::new(iterate_maps(&plane_levers, &parsed_input.values).fold(
-            HashMap::new(),
-            move |mut properties, (k, a, w)| {
-                properties.insert(*k, WeightLever::new(*w, *a));
-                properties
+/// Answers requests until the client closes the connection, it is idle for
+/// too long or the server shuts down.
+async fn process(
+    stream: impl AsyncRead + AsyncWrite,
+    peer: SocketAddr,
+    permit: Option<OwnedSemaphorePermit>,
+    mut shared: Shared,
+) -> io::Result<()> {
+    let (reader, mut writer) = split(stream);
+    let Some(_permit) = permit else {
+        write_message(&mut writer, &Response::error("Server busy")).await?;
+        return Err(io::Error::other("Too many connections, turned away"));
+    };
+    let mut reader = BufReader::new(reader);
+    loop {
+        let request = tokio::select! {
+            request = timeout(shared.idle, read_message::<Request>(&mut reader)) => match request {
+                Ok(Err(e)) if MessageTooLong::is(&e) => {
+                    write_message(&mut writer, &Response::error("Message too long")).await?;
+                    return Err(e);
+                }
+                Ok(request) => request?,
+                Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "Connection idle")),
             },
-        ));
+            _ = shared.stopping.changed() => return Ok(()),
+        };
+        let Some(request) = request else {
+            debug!(peer:% = peer; "Client disconnected");
+            return Ok(());
+        };
+        let response = handle(request, &shared, peer).await;
+        write_message(&mut writer, &response).await?;
+    }
+}
 
-    plane_config.is_weight_and_balance_ok(&plane_properties)
+/// Calculates the changes to a session one after the other and sends the
+/// new state to all participants. Ends when the last participant has left.
+async fn run_session(
+    id: String,
+    mut changes: mpsc::Receiver<Change>,
+    updates: broadcast::Sender<String>,
+    shared: Shared,
+) {
+    let mut session = Session::default();
+    while let Some(change) = changes.recv().await {
+        if let Change::Request(request, reply) = change {
+            let request_id = shared.next_request_id();
+            let started = Instant::now();
+            let result = match session.change(request) {
+                Ok(input) => {
+                    let planes = shared.config.borrow().clone();
+                    let lookups = shared.lookups.clone();
+                    let loading = input.clone();
+                    blocking(move || calc_partial(loading, &planes, &lookups))
+                        .await
+                        .map(|outcome| session.commit(input, outcome))
+                }
+                Err(e) => Err(e),
+            };
+            let elapsed = started.elapsed();
+            let micros = elapsed.as_micros() as u64;
+            shared.metrics.request("session", result.is_ok(), elapsed);
+            match &result {
+                Ok(()) => {
+                    let plane = session.input.name.as_str();
+                    if let Some(outcome) = &session.outcome {
+                        shared.metrics.outcome(plane, outcome);
+                    }
+                    info!(
+                        request_id = request_id, session = id, plane = plane,
+                        revision = session.revision, micros = micros;
+                        "Session changed"
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        request_id = request_id, session = id, micros = micros;
+                        "Session change failed: {}", e
+                    );
+                }
+            }
+            let failed = result.is_err();
+            let _ = reply.send(result);
+            if failed {
+                continue;
+            }
+        }
+        let state = session.state(updates.receiver_count());
+        let _ = updates.send(serde_json::to_string(&state).expect("State is serializable"));
+    }
+}
+
+fn reject(status: StatusCode, message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = status;
+    response
 }
 
-async fn process(mut socket: TcpStream) -> io::Result<()> {
-    let mut buf = Vec::with_capacity(1024);
-    let n = socket.read_buf(&mut buf).await?;
-    if n == 0 {
-        println!("Client disconnected");
-        return Ok(());
+/// Joins the session in the WebSocket's path, `/sessions/<id>`, with the
+/// token in the query, `?token=secret`, when the server requires one.
+async fn join_session(
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer: SocketAddr,
+    permit: Option<OwnedSemaphorePermit>,
+    shared: Shared,
+) -> io::Result<()> {
+    let mut id = String::new();
+    // The callback's error type is given by tungstenite.
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &HandshakeRequest, response: HandshakeResponse| {
+        let uri = request.uri();
+        let token = uri.query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+        });
+        match uri.path().strip_prefix("/sessions/") {
+            Some(path) if !path.is_empty() && !path.contains('/') => {
+                if !is_authorized(token, &shared.tokens) {
+                    return Err(reject(StatusCode::UNAUTHORIZED, "Unauthorized"));
+                }
+                id = path.to_string();
+                Ok(response)
+            }
+            _ => Err(reject(
+                StatusCode::NOT_FOUND,
+                "Sessions are at /sessions/<id>",
+            )),
+        }
+    };
+    let mut socket = match timeout(
+        shared.idle,
+        tokio_tungstenite::accept_hdr_async(stream, callback),
+    )
+    .await
+    {
+        Ok(socket) => socket.map_err(io::Error::other)?,
+        Err(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Handshake timed out",
+            ))
+        }
+    };
+    let Some(_permit) = permit else {
+        let busy = SessionUpdate::Error {
+            message: "Server busy".to_string(),
+        };
+        let busy = serde_json::to_string(&busy).expect("Update is serializable");
+        socket
+            .send(Message::text(busy))
+            .await
+            .map_err(io::Error::other)?;
+        let _ = socket.close(None).await;
+        return Err(io::Error::other("Too many connections, turned away"));
+    };
+
+    // Subscribing and leaving under the lock, so a session is only removed
+    // when nobody is about to join it.
+    let (handle, updates) = {
+        let mut sessions = shared.sessions.lock().expect("Sessions poisoned");
+        let handle = sessions
+            .entry(id.clone())
+            .or_insert_with(|| {
+                let (changes, received) = mpsc::channel(16);
+                let (updates, _) = broadcast::channel(16);
+                tokio::spawn(run_session(
+                    id.clone(),
+                    received,
+                    updates.clone(),
+                    shared.clone(),
+                ));
+                SessionHandle { changes, updates }
+            })
+            .clone();
+        let updates = handle.updates.subscribe();
+        (handle, updates)
+    };
+    info!(session = id, peer:% = peer; "Participant joined");
+    let _ = handle.changes.send(Change::Participants).await;
+    let result = participate(&mut socket, &handle, updates, shared.stopping).await;
+
+    let _ = handle.changes.send(Change::Participants).await;
+    let mut sessions = shared.sessions.lock().expect("Sessions poisoned");
+    if handle.updates.receiver_count() == 0 {
+        sessions.remove(&id);
     }
-    match String::from_utf8(buf[0..n].to_vec()) {
-        Ok(str) => {
-            let json = serde_json::from_str::<Input>(&str);
-            match json {
-                Ok(data) => {
-                    let wb = calc(data);
-                    send_reply(wb, socket).await?;
+    result
+}
+
+/// Passes the participant's changes to the session and the session's updates
+/// to the participant, until either side closes or the server shuts down.
+async fn participate(
+    socket: &mut WebSocketStream<impl AsyncRead + AsyncWrite + Unpin>,
+    handle: &SessionHandle,
+    mut updates: broadcast::Receiver<String>,
+    mut stopping: watch::Receiver<bool>,
+) -> io::Result<()> {
+    loop {
+        tokio::select! {
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let result = match serde_json::from_str::<SessionRequest>(&text) {
+                        Ok(request) => {
+                            let (reply, replied) = oneshot::channel();
+                            let _ = handle.changes.send(Change::Request(request, reply)).await;
+                            replied.await.unwrap_or_else(|_| Err("Session closed".to_string()))
+                        }
+                        Err(e) => Err(format!("Invalid message: {}", e)),
+                    };
+                    if let Err(message) = result {
+                        let error = serde_json::to_string(&SessionUpdate::Error { message })
+                            .expect("Update is serializable");
+                        socket.send(Message::text(error)).await.map_err(io::Error::other)?;
+                    }
                 }
-                Err(e) => {
-                    error!("Failed to parsed json: {}", e);
-                    return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                // Pings are answered by the WebSocket itself.
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(io::Error::other(e)),
+            },
+            update = updates.recv() => match update {
+                Ok(update) => socket.send(Message::text(update)).await.map_err(io::Error::other)?,
+                // Only the latest state matters, it is still to come.
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            _ = stopping.changed() => {
+                let _ = socket.close(None).await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Answers one HTTP request, `GET /metrics` with the metrics in the
+/// Prometheus text format. The token goes in `Authorization: Bearer <token>`
+/// when the server requires one.
+async fn serve_metrics(
+    stream: impl AsyncRead + AsyncWrite,
+    permit: Option<OwnedSemaphorePermit>,
+    shared: Shared,
+) -> io::Result<()> {
+    let (reader, mut writer) = split(stream);
+    let mut reader = BufReader::new(reader.take(16 * 1024));
+    let mut request_line = String::new();
+    let mut token = None;
+    let read_head = async {
+        reader.read_line(&mut request_line).await?;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+                return io::Result::Ok(());
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("authorization") {
+                    token = value.trim().strip_prefix("Bearer ").map(str::to_string);
                 }
             }
         }
-        Err(e) => {
-            error!("Failed to parse json");
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+    };
+    match timeout(shared.idle, read_head).await {
+        Ok(head) => head?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "Connection idle")),
+    }
+
+    let request: Vec<&str> = request_line.split_whitespace().take(2).collect();
+    let (status, body) = match request[..] {
+        _ if permit.is_none() => ("503 Service Unavailable", "Server busy\n".to_string()),
+        ["GET", "/metrics"] if !is_authorized(token.as_deref(), &shared.tokens) => {
+            ("401 Unauthorized", "Unauthorized\n".to_string())
         }
+        ["GET", "/metrics"] => ("200 OK", shared.metrics.render()),
+        _ => ("404 Not Found", "Metrics are at GET /metrics\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+async fn connection(
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    endpoint: Endpoint,
+    peer: SocketAddr,
+    permit: Option<OwnedSemaphorePermit>,
+    shared: Shared,
+) -> io::Result<()> {
+    match endpoint {
+        Endpoint::Requests => process(stream, peer, permit, shared).await,
+        Endpoint::Sessions => join_session(stream, peer, permit, shared).await,
+        Endpoint::Metrics => serve_metrics(stream, permit, shared).await,
     }
+}
 
-    Ok(())
+async fn accept(listener: Option<&TcpListener>) -> io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
 }
 
-async fn send_reply(wb: Result<(), FailReason>, mut socket: TcpStream) -> Result<(), io::Error> {
-    if wb.is_ok() {
-        socket.write_all(b"W&B is OK").await?;
-    } else {
-        let reply = format!("W&B failed due to: {:?}", wb.unwrap_err());
-        socket.write_all(reply.as_bytes()).await?;
+/// Accepts connections until `shutdown` completes, then waits for the open
+/// connections to finish their current request.
+async fn serve(
+    listeners: Listeners,
+    args: Args,
+    settings: ServerConfig,
+    config: watch::Receiver<Planes>,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let acceptor = match &settings.tls {
+        Some(files) => Some(
+            files
+                .acceptor()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        ),
+        None => None,
+    };
+    let permits = Arc::new(Semaphore::new(args.max_connections));
+    let mut connections = JoinSet::new();
+    let (stopping, _) = watch::channel(false);
+    let shared = Shared {
+        idle: Duration::from_secs(args.timeout),
+        config,
+        lookups: Arc::new(Lookups::read(&args.profiles, &args.mass_tables)),
+        stopping: stopping.subscribe(),
+        tokens: Arc::new(settings.tokens),
+        sessions: Sessions::default(),
+        metrics: Arc::default(),
+        request_ids: Arc::default(),
+    };
+    tokio::pin!(shutdown);
+
+    loop {
+        let (accepted, endpoint) = tokio::select! {
+            accepted = listeners.requests.accept() => (accepted, Endpoint::Requests),
+            accepted = accept(listeners.sessions.as_ref()) => (accepted, Endpoint::Sessions),
+            accepted = accept(listeners.metrics.as_ref()) => (accepted, Endpoint::Metrics),
+            _ = &mut shutdown => break,
+        };
+        let (socket, address) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        while connections.try_join_next().is_some() {}
+
+        // Connections above the maximum are still answered, over TLS if
+        // enabled, with an error response.
+        let permit = permits.clone().try_acquire_owned().ok();
+        let acceptor = acceptor.clone();
+        let shared = shared.clone();
+        connections.spawn(async move {
+            let result = match acceptor {
+                Some(acceptor) => match timeout(shared.idle, acceptor.accept(socket)).await {
+                    Ok(Ok(stream)) => connection(stream, endpoint, address, permit, shared).await,
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "TLS handshake timed out",
+                    )),
+                },
+                None => connection(socket, endpoint, address, permit, shared).await,
+            };
+            if let Err(e) = result {
+                error!("Connection from {} failed: {}", address, e);
+            }
+        });
     }
+
+    while connections.try_join_next().is_some() {}
+    let _ = stopping.send(true);
+    info!(
+        "Shutting down, waiting for {} connections",
+        connections.len()
+    );
+    while connections.join_next().await.is_some() {}
     Ok(())
 }
 
+/// Checks the config file for changes every `interval` and swaps in each new
+/// config that is valid. An invalid config is logged once and the last good
+/// config is kept.
+async fn watch_config(
+    path: String,
+    interval: Duration,
+    config: watch::Sender<Planes>,
+    mut last_hash: String,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                if !last_hash.is_empty() {
+                    error!(
+                        "Keeping the last good config, could not read {}: {}",
+                        path, e
+                    );
+                    // Checked again once it can be read, whatever it holds.
+                    last_hash.clear();
+                }
+                continue;
+            }
+        };
+        let hash = config_hash(contents.as_bytes());
+        if hash == last_hash {
+            continue;
+        }
+        last_hash = hash;
+        match parse_plane_config(&contents) {
+            Ok(planes) => {
+                info!("Reloaded config {} with {} planes", path, planes.len());
+                config.send_replace(Arc::new(planes));
+            }
+            Err(e) => error!("Keeping the last good config, {} is invalid: {}", path, e),
+        }
+    }
+}
+
+async fn bind(address: Option<&str>) -> Option<TcpListener> {
+    match address {
+        Some(address) => Some(
+            TcpListener::bind(address)
+                .await
+                .unwrap_or_else(|e| panic!("Could not listen on {}: {}", address, e)),
+        ),
+        None => None,
+    }
+}
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    // Bind the listener to the address
-    let listener = TcpListener::bind("127.0.0.1:8080")
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let contents = std::fs::read_to_string(&args.config).expect("Config not found");
+    let planes = parse_plane_config(&contents).unwrap_or_else(|e| panic!("{}", e));
+    let (config, current) = watch::channel(Arc::new(planes));
+    tokio::spawn(watch_config(
+        args.config.clone(),
+        Duration::from_secs(args.reload_interval),
+        config,
+        config_hash(contents.as_bytes()),
+    ));
+    let settings = args
+        .server_config
+        .as_deref()
+        .map(read_server_config)
+        .unwrap_or_default();
+    let listeners = Listeners {
+        requests: bind(Some(&args.address)).await.expect("Address is set"),
+        sessions: bind(args.session_address.as_deref()).await,
+        metrics: bind(args.metrics_address.as_deref()).await,
+    };
+
+    serve(listeners, args, settings, current, shutdown_signal()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufRead, BufReader},
+        net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+        task::JoinHandle,
+    };
+
+    use super::*;
+    use wbl::protocol::MAX_MESSAGE_LENGTH;
+
+    async fn start(
+        args: Args,
+        settings: ServerConfig,
+    ) -> (String, oneshot::Sender<()>, JoinHandle<()>) {
+        start_with(args, settings, None, None).await
+    }
+
+    async fn start_with(
+        args: Args,
+        settings: ServerConfig,
+        sessions: Option<TcpListener>,
+        metrics: Option<TcpListener>,
+    ) -> (String, oneshot::Sender<()>, JoinHandle<()>) {
+        let listeners = Listeners {
+            requests: TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            sessions,
+            metrics,
+        };
+        let address = listeners.requests.local_addr().unwrap().to_string();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let config = parse_plane_config(include_str!("../input/config.json")).unwrap();
+        let (_, config) = watch::channel(Arc::new(config));
+        let server = tokio::spawn(async move {
+            serve(listeners, args, settings, config, async {
+                let _ = stopped.await;
+            })
+            .await
+            .unwrap();
+        });
+        (address, stop, server)
+    }
+
+    fn args() -> Args {
+        Args {
+            address: String::new(),
+            timeout: 5,
+            max_connections: 4,
+            config: String::new(),
+            profiles: String::new(),
+            mass_tables: String::new(),
+            reload_interval: 1,
+            server_config: None,
+            session_address: None,
+            metrics_address: None,
+        }
+    }
+
+    async fn connect(address: &str) -> (BufReader<OwnedReadHalf>, OwnedWriteHalf) {
+        let (reader, writer) = TcpStream::connect(address).await.unwrap().into_split();
+        (BufReader::new(reader), writer)
+    }
+
+    async fn response(reader: &mut (impl AsyncBufRead + Unpin)) -> ResponseBody {
+        read_message::<Response>(reader)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+            .body
+    }
+
+    fn error(message: &str) -> ResponseBody {
+        ResponseBody::Error {
+            message: message.to_string(),
+        }
+    }
+
+    const UNKNOWN_PLANE: &str =
+        r#"{"version":1,"type":"calculate","input":{"name":"NOPLANE","values":{}}}"#;
+
+    #[tokio::test]
+    async fn slow_client_does_not_block() {
+        let (address, stop, server) = start(args(), ServerConfig::default()).await;
+        let slow = TcpStream::connect(&address).await.unwrap();
+        let (mut reader, mut writer) = connect(&address).await;
+
+        writer
+            .write_all(format!("{}\n", UNKNOWN_PLANE).as_bytes())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response(&mut reader).await,
+            error("Plane missing in config: NOPLANE")
+        );
+        drop(slow);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn many_requests_per_connection() {
+        let (address, stop, server) = start(args(), ServerConfig::default()).await;
+        let (mut reader, mut writer) = connect(&address).await;
+        let (first, second) = UNKNOWN_PLANE.split_at(30);
+
+        writer.write_all(b"not json\n").await.unwrap();
+        writer.write_all(first.as_bytes()).await.unwrap();
+        writer.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        writer
+            .write_all(format!("{}\n", second).as_bytes())
+            .await
+            .unwrap();
+        writer
+            .write_all(b"{\"version\":1,\"type\":\"calculate\",\"input\":{\"name\":\"SEMOA\",\"values\":{}}}\n")
+            .await
+            .unwrap();
+        writer
+            .write_all(b"{\"version\":0,\"type\":\"calculate\",\"input\":{\"name\":\"SEMOA\",\"values\":{}}}\n")
+            .await
+            .unwrap();
+
+        assert!(matches!(response(&mut reader).await,
+            ResponseBody::Error { message } if message.starts_with("Invalid message")));
+        assert_eq!(
+            response(&mut reader).await,
+            error("Plane missing in config: NOPLANE")
+        );
+        // Only the base has a default loading.
+        assert_eq!(
+            response(&mut reader).await,
+            error("Missing weight for station: pilot")
+        );
+        assert_eq!(
+            response(&mut reader).await,
+            error("Unsupported protocol version 0, expected 1")
+        );
+        drop(writer);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_too_long_answered() {
+        let (address, stop, server) = start(args(), ServerConfig::default()).await;
+        let (mut reader, mut writer) = connect(&address).await;
+
+        writer
+            .write_all(&vec![b' '; MAX_MESSAGE_LENGTH as usize])
+            .await
+            .unwrap();
+
+        assert_eq!(response(&mut reader).await, error("Message too long"));
+        assert!(read_message::<Response>(&mut reader)
+            .await
+            .unwrap()
+            .is_none());
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn profiles_read_at_startup() {
+        let path = std::env::temp_dir().join(format!("wbl-profiles-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"people":{"anna":{"weight":62.0}}}"#).unwrap();
+        let (address, stop, server) = start(
+            Args {
+                profiles: path.to_str().unwrap().to_string(),
+                ..args()
+            },
+            ServerConfig::default(),
+        )
+        .await;
+        let (mut reader, mut writer) = connect(&address).await;
+        let request = |pilot: &str| {
+            format!(
+                r#"{{"version":1,"type":"calculate","input":{{"name":"SEMOA","values":{{"pilot":"{}","fuel":60}}}}}}"#,
+                pilot
+            ) + "\n"
+        };
+
+        writer.write_all(request("@anna").as_bytes()).await.unwrap();
+        let first = response(&mut reader).await;
+        assert!(matches!(first, ResponseBody::Outcome { .. }), "{:?}", first);
+        // Not read again for each request.
+        std::fs::remove_file(&path).unwrap();
+        writer.write_all(request("@anna").as_bytes()).await.unwrap();
+        writer
+            .write_all(request("@bertil").as_bytes())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            response(&mut reader).await,
+            ResponseBody::Outcome { .. }
+        ));
+        assert_eq!(
+            response(&mut reader).await,
+            error("Unknown profile: bertil")
+        );
+        drop(writer);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn mass_tables_read_at_startup() {
+        let path =
+            std::env::temp_dir().join(format!("wbl-mass-tables-{}.json", std::process::id()));
+        std::fs::write(&path, r#"[{"name":"club","masses":{"adult":95.0}}]"#).unwrap();
+        let (address, stop, server) = start(
+            Args {
+                mass_tables: path.to_str().unwrap().to_string(),
+                ..args()
+            },
+            ServerConfig::default(),
+        )
+        .await;
+        let (mut reader, mut writer) = connect(&address).await;
+        let request = |station: &str, category: &str| {
+            format!(
+                r#"{{"version":1,"type":"calculate","input":{{"name":"SEMOA","mass_table":"club","values":{{"pilot":80,"fuel":60,"{}":"{}"}}}}}}"#,
+                station, category
+            ) + "\n"
+        };
+
+        writer
+            .write_all(request("co_pilot", "adult").as_bytes())
+            .await
+            .unwrap();
+        let first = response(&mut reader).await;
+        assert!(matches!(first, ResponseBody::Outcome { .. }), "{:?}", first);
+        // Not read again for each request.
+        std::fs::remove_file(&path).unwrap();
+        for (station, category) in [
+            ("co_pilot", "adult"),
+            ("co_pilot", "pilot"),
+            ("base", "adult"),
+        ] {
+            writer
+                .write_all(request(station, category).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            response(&mut reader).await,
+            ResponseBody::Outcome { .. }
+        ));
+        assert_eq!(
+            response(&mut reader).await,
+            error("Invalid passenger category: pilot")
+        );
+        assert_eq!(
+            response(&mut reader).await,
+            error("Passenger categories are only for seats: base")
+        );
+        drop(writer);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_idle_connections() {
+        let (address, stop, server) = start(args(), ServerConfig::default()).await;
+        let (mut reader, _writer) = connect(&address).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+        assert!(read_message::<Response>(&mut reader)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn turns_away_above_max_connections() {
+        let (address, stop, server) = start(
+            Args {
+                max_connections: 1,
+                ..args()
+            },
+            ServerConfig::default(),
+        )
+        .await;
+        let slow = TcpStream::connect(&address).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (mut reader, _writer) = connect(&address).await;
+
+        assert_eq!(response(&mut reader).await, error("Server busy"));
+        drop(slow);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_requires_token() {
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let cert = dir.join(format!("wbl-server-{}-cert.pem", std::process::id()));
+        let private = dir.join(format!("wbl-server-{}-key.pem", std::process::id()));
+        std::fs::write(&cert, key.cert.pem()).unwrap();
+        std::fs::write(&private, key.key_pair.serialize_pem()).unwrap();
+        let cert = cert.to_str().unwrap().to_string();
+        let settings = ServerConfig {
+            tls: Some(TlsFiles {
+                cert: cert.clone(),
+                key: private.to_str().unwrap().to_string(),
+            }),
+            tokens: vec!["secret".to_string()],
+        };
+        let (address, stop, server) = start(args(), settings).await;
+        let connector = wbl::tls::connector(&cert).unwrap();
+        let socket = TcpStream::connect(&address).await.unwrap();
+        let stream = connector
+            .connect(wbl::tls::server_name("localhost:0").unwrap(), socket)
+            .await
+            .unwrap();
+        let (reader, mut writer) = split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut request: Request = serde_json::from_str(UNKNOWN_PLANE).unwrap();
+
+        write_message(&mut writer, &request).await.unwrap();
+        request.token = Some("wrong".to_string());
+        write_message(&mut writer, &request).await.unwrap();
+        request.token = Some("secret".to_string());
+        write_message(&mut writer, &request).await.unwrap();
+
+        assert_eq!(response(&mut reader).await, error("Unauthorized"));
+        assert_eq!(response(&mut reader).await, error("Unauthorized"));
+        assert_eq!(
+            response(&mut reader).await,
+            error("Plane missing in config: NOPLANE")
+        );
+        drop(writer);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+        let _ = std::fs::remove_file(&cert);
+        let _ = std::fs::remove_file(&private);
+    }
+
+    async fn update<S: AsyncRead + AsyncWrite + Unpin>(
+        socket: &mut WebSocketStream<S>,
+    ) -> serde_json::Value {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn session_shares_changes() {
+        let sessions = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/sessions/", sessions.local_addr().unwrap());
+        let settings = ServerConfig {
+            tokens: vec!["secret".to_string()],
+            ..Default::default()
+        };
+        let (_, stop, server) = start_with(args(), settings, Some(sessions), None).await;
+
+        assert!(tokio_tungstenite::connect_async(format!("{}flight", url))
+            .await
+            .is_err());
+        let url = format!("{}flight?token=secret", url);
+        let (mut pilot, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        assert_eq!(update(&mut pilot).await["participants"], 1);
+        let (mut desk, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        assert_eq!(update(&mut pilot).await["participants"], 2);
+        assert_eq!(update(&mut desk).await["participants"], 2);
+
+        pilot
+            .send(Message::text(r#"{"type":"plane","name":"SEMOA"}"#))
+            .await
+            .unwrap();
+        for socket in [&mut pilot, &mut desk] {
+            let state = update(socket).await;
+            assert_eq!(state["revision"], 1);
+            assert_eq!(state["input"]["name"], "SEMOA");
+            // Not calculated until pilot and fuel have a weight.
+            assert!(state["outcome"].is_null());
+        }
+        desk.send(Message::text(
+            r#"{"type":"stations","values":{"pilot":82,"fuel":"60L"}}"#,
+        ))
+        .await
+        .unwrap();
+        for socket in [&mut pilot, &mut desk] {
+            let state = update(socket).await;
+            assert_eq!(state["revision"], 2);
+            assert_eq!(state["input"]["values"]["pilot"], 82);
+            assert!(state["outcome"]["point"]["weight"].as_f64().unwrap() > 500.0);
+        }
+        desk.send(Message::text(
+            r#"{"type":"stations","values":{"pilot":true}}"#,
+        ))
         .await
-        .expect("Could not create listener");
+        .unwrap();
+        let error = update(&mut desk).await;
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["message"], "Expected a weight in kg for pilot: true");
 
-    loop {
-        // The second item contains the IP and port of the new connection.
-        let (socket, _) = listener.accept().await?;
-        process(socket).await?;
+        desk.close(None).await.unwrap();
+        assert_eq!(update(&mut pilot).await["participants"], 1);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+        assert!(matches!(
+            pilot.next().await,
+            Some(Ok(Message::Close(_))) | None
+        ));
+    }
+
+    async fn get_metrics(address: &str, authorization: &str) -> String {
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET /metrics HTTP/1.1\r\n{}\r\n", authorization);
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests() {
+        let metrics = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let metrics_address = metrics.local_addr().unwrap().to_string();
+        let settings = ServerConfig {
+            tokens: vec!["secret".to_string()],
+            ..Default::default()
+        };
+        let (address, stop, server) = start_with(args(), settings, None, Some(metrics)).await;
+        let (mut reader, mut writer) = connect(&address).await;
+        let mut request: Request = serde_json::from_str(UNKNOWN_PLANE).unwrap();
+        request.token = Some("secret".to_string());
+        write_message(&mut writer, &request).await.unwrap();
+        let RequestBody::Calculate { input } = &mut request.body else {
+            unreachable!()
+        };
+        input.name = "SEMOA".to_string();
+        input.values.insert("pilot".to_string(), 82.into());
+        input.values.insert("fuel".to_string(), 60.into());
+        write_message(&mut writer, &request).await.unwrap();
+        response(&mut reader).await;
+        response(&mut reader).await;
+
+        assert!(get_metrics(&metrics_address, "")
+            .await
+            .starts_with("HTTP/1.1 401"));
+        let metrics = get_metrics(&metrics_address, "Authorization: Bearer secret\r\n").await;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+        for line in [
+            "wbl_requests_total{type=\"calculate\",result=\"ok\"} 1",
+            "wbl_requests_total{type=\"calculate\",result=\"error\"} 1",
+            "wbl_request_duration_seconds_count{type=\"calculate\"} 2",
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "{} missing", line);
+        }
+        assert!(metrics.contains("wbl_checks_total{plane=\"SEMOA\""));
+        drop(writer);
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_last_good_config() {
+        let path = std::env::temp_dir().join(format!("wbl-reload-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let contents = include_str!("../input/config.json");
+        std::fs::write(&path, contents).unwrap();
+        let (config, current) = watch::channel(Arc::new(parse_plane_config(contents).unwrap()));
+        let planes = current.borrow().len();
+        tokio::spawn(watch_config(
+            path.clone(),
+            Duration::from_millis(10),
+            config,
+            config_hash(contents.as_bytes()),
+        ));
+
+        std::fs::write(&path, "[]").unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(current.borrow().len(), planes);
+
+        let mut one_plane = serde_json::from_str::<Vec<serde_json::Value>>(contents).unwrap();
+        one_plane.truncate(1);
+        std::fs::write(&path, serde_json::to_string(&one_plane).unwrap()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(current.borrow().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(current.borrow().len(), 1);
+        std::fs::write(&path, contents).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(current.borrow().len(), planes);
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -1,8 +1,12 @@
 use crate::{planes::PlaneProperties, FailReason, WeightLever};
 
+/// Checks and W&B points of a loading. The properties must weigh something,
+/// as those from `PlaneData::properties` do.
 pub trait WeightAndBalance {
-    fn is_weight_and_balance_ok(&self, prop: &PlaneProperties) ->  Result<(), FailReason>;
+    fn is_weight_and_balance_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason>;
     fn is_landing_weight_and_balance_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason>;
     fn calc_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever;
     fn calc_landing_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever;
+    fn calc_lateral_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever;
+    fn calc_landing_lateral_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever;
 }
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calc_wb::WeightAndBalance,
+    planes::{PlaneData, PlaneProperties},
+    Kind, WeightLever,
+};
+
+const TAIL_BALLAST_STEP: f64 = 0.1;
+
+/// Glider specific limits. The cockpit load is the weight in the pilot seat,
+/// including parachute and trim ballast.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GliderData {
+    pub wing_area: f64,
+    pub min_cockpit_load: f64,
+    pub max_cockpit_load: f64,
+    pub max_water_ballast: Option<f64>,
+    pub max_tail_ballast: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BallastRecommendation {
+    /// Trim ballast needed in the cockpit to reach the minimum cockpit load.
+    pub cockpit_ballast: f64,
+    /// Most water ballast that fits in the tanks without exceeding MTOW.
+    pub max_water_ballast: f64,
+    /// Most tail ballast that keeps the W&B ok, or `None` if no amount does.
+    pub max_tail_ballast: Option<f64>,
+}
+
+/// Wing loading in kg/m², or `None` if the plane is not a glider.
+pub fn wing_loading(plane: &PlaneData, prop: &PlaneProperties) -> Option<f64> {
+    plane
+        .glider
+        .as_ref()
+        .map(|glider| prop.get_total_weights() / glider.wing_area)
+}
+
+/// Recommends ballast for the pilot weight and water ballast in `prop`.
+/// Returns `None` if the plane is not a glider.
+pub fn recommend_ballast(
+    plane: &PlaneData,
+    prop: &PlaneProperties,
+) -> Option<BallastRecommendation> {
+    let glider = plane.glider.as_ref()?;
+    let station_weight =
+        |prop: &PlaneProperties, kind| prop.get(&kind).map(|wb| wb.weight).unwrap_or(0.0);
+
+    let cockpit_ballast = (glider.min_cockpit_load - station_weight(prop, Kind::Pilot)).max(0.0);
+    let mut ballasted = prop.clone();
+    if cockpit_ballast > 0.0 {
+        let pilot = prop
+            .get(&Kind::Pilot)
+            .copied()
+            .unwrap_or(WeightLever::new(0.0, plane.levers.pilot));
+        ballasted.insert(
+            Kind::Pilot,
+            WeightLever::new(pilot.weight + cockpit_ballast, pilot.lever),
+        );
+    }
+
+    let max_water_ballast = glider
+        .max_water_ballast
+        .map(|max_water| {
+            let dry_weight =
+                ballasted.get_total_weights() - station_weight(&ballasted, Kind::WaterBallast);
+            max_water
+                .min(plane.max_weights.max_take_off_weight - dry_weight)
+                .max(0.0)
+        })
+        .unwrap_or(0.0);
+
+    let max_tail_ballast = match (glider.max_tail_ballast, plane.levers.tail_ballast) {
+        (Some(max_tail), Some(lever)) => {
+            let steps = (max_tail / TAIL_BALLAST_STEP).floor() as u32;
+            (0..=steps)
+                .rev()
+                .map(|step| step as f64 * TAIL_BALLAST_STEP)
+                .find(|tail| {
+                    let mut loaded = ballasted.clone();
+                    loaded.insert(Kind::TailBallast, WeightLever::new(*tail, lever));
+                    plane.is_weight_and_balance_ok(&loaded).is_ok()
+                })
+        }
+        _ => None,
+    };
+
+    Some(BallastRecommendation {
+        cockpit_ballast,
+        max_water_ballast,
+        max_tail_ballast,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::FailReason;
+
+    fn glider() -> PlaneData {
+        let mut plane = PlaneData::default();
+        plane.levers.tail_ballast = Some(600.0);
+        plane.max_weights.max_take_off_weight = 525.0;
+        plane.vertices = [
+            [250.0, 20.0],
+            [400.0, 20.0],
+            [525.0, 20.0],
+            [525.0, 40.0],
+            [400.0, 40.0],
+            [250.0, 40.0],
+        ];
+        plane.glider = Some(GliderData {
+            wing_area: 10.5,
+            min_cockpit_load: 70.0,
+            max_cockpit_load: 110.0,
+            max_water_ballast: Some(150.0),
+            max_tail_ballast: Some(5.0),
+        });
+        plane
+    }
+
+    fn loading(pilot: f64) -> PlaneProperties {
+        PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(250.0, 40.0)),
+            (Kind::Pilot, WeightLever::new(pilot, -50.0)),
+            (Kind::WaterBallast, WeightLever::new(0.0, 30.0)),
+        ]))
+    }
+
+    #[test]
+    fn wing_loading_glider() {
+        let prop = loading(65.0);
+
+        assert_eq!(wing_loading(&glider(), &prop), Some(30.0));
+    }
+
+    #[test]
+    fn cockpit_load_too_low() {
+        let prop = loading(65.0);
+
+        assert_eq!(
+            glider().is_weight_and_balance_ok(&prop).unwrap_err(),
+            FailReason::MinCockpitLoad
+        );
+    }
+
+    #[test]
+    fn recommend_ballast_light_pilot() {
+        let prop = loading(65.0);
+        let recommendation = recommend_ballast(&glider(), &prop).unwrap();
+
+        assert_eq!(recommendation.cockpit_ballast, 5.0);
+        assert_eq!(recommendation.max_water_ballast, 150.0);
+        assert_eq!(recommendation.max_tail_ballast, Some(5.0));
+    }
+
+    #[test]
+    fn recommend_tail_ballast_up_to_aft_limit() {
+        let mut plane = glider();
+        plane.glider.as_mut().unwrap().max_tail_ballast = Some(20.0);
+        let recommendation = recommend_ballast(&plane, &loading(65.0)).unwrap();
+
+        // The CG reaches the aft limit of 40 with 11.25 kg in the tail.
+        let tail_ballast = recommendation.max_tail_ballast.unwrap();
+        assert!((tail_ballast - 11.2).abs() < 1e-9, "{}", tail_ballast);
+    }
+
+    #[test]
+    fn recommend_ballast_without_pilot() {
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(250.0, 40.0))]));
+        let recommendation = recommend_ballast(&glider(), &prop).unwrap();
+
+        assert_eq!(recommendation.cockpit_ballast, 70.0);
+    }
+
+    #[test]
+    fn recommend_ballast_not_glider() {
+        let prop = loading(65.0);
+
+        assert_eq!(recommend_ballast(&PlaneData::default(), &prop), None);
+    }
+}
@@ -0,0 +1,89 @@
+use eframe::egui;
+use wbl::profiles::{write_profiles, Profiles};
+
+/// Window to edit the saved profiles, written to `path` on save.
+struct ProfileEditor {
+    path: String,
+    profiles: Profiles,
+    /// Name typed for the next profile to add.
+    new_name: String,
+    status: String,
+}
+
+impl eframe::App for ProfileEditor {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Clothing allowance");
+                ui.add(weight(&mut self.profiles.clothing_allowance));
+            });
+            ui.separator();
+
+            let mut removed = None;
+            egui::Grid::new("profiles").striped(true).show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Weight");
+                ui.strong("Bagage");
+                ui.end_row();
+                for (name, profile) in self.profiles.people.iter_mut() {
+                    ui.label(format!("@{}", name));
+                    ui.add(weight(&mut profile.weight));
+                    ui.add(weight(&mut profile.bagage));
+                    if ui.button("Remove").clicked() {
+                        removed = Some(name.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(name) = removed {
+                self.profiles.people.remove(&name);
+            }
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_name);
+                let name = self.new_name.trim().trim_start_matches('@').to_string();
+                let add = egui::Button::new("Add");
+                if ui.add_enabled(!name.is_empty(), add).clicked() {
+                    let profile = self.profiles.people.get(&name).cloned().unwrap_or_default();
+                    match self.profiles.set(&name, profile) {
+                        Ok(()) => self.new_name.clear(),
+                        Err(e) => self.status = e,
+                    }
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.status = match write_profiles(&self.path, &self.profiles) {
+                        Ok(()) => format!("Saved to {}", self.path),
+                        Err(e) => format!("Could not save: {}", e),
+                    };
+                }
+                ui.label(&self.status);
+            });
+        });
+    }
+}
+
+fn weight(value: &mut f64) -> egui::DragValue<'_> {
+    egui::DragValue::new(value)
+        .clamp_range(0.0..=500.0)
+        .speed(0.5)
+        .suffix(" kg")
+}
+
+/// Opens a window to edit the profiles, until it is closed.
+pub fn edit_profiles(path: &str, profiles: Profiles) -> Result<(), eframe::Error> {
+    let editor = ProfileEditor {
+        path: path.to_string(),
+        profiles,
+        new_name: String::new(),
+        status: String::new(),
+    };
+    eframe::run_native(
+        "Profiles",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Box::new(editor)),
+    )
+}
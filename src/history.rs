@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calc_wb::WeightAndBalance,
+    planes::{PlaneData, PlaneProperties},
+    stations::fill_stations,
+    FailReason, Kind, WeightLever,
+};
+
+/// Result of a W&B calculation, `None` means approved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub take_off: Option<FailReason>,
+    pub landing: Option<FailReason>,
+    pub point: WeightLever,
+    pub landing_point: WeightLever,
+    /// Revision of the plane's config the outcome was calculated with.
+    #[serde(default)]
+    pub revision: Option<String>,
+}
+
+impl Outcome {
+    pub fn calc(plane: &PlaneData, prop: &PlaneProperties) -> Outcome {
+        Outcome {
+            take_off: plane.is_weight_and_balance_ok(prop).err(),
+            landing: plane.is_landing_weight_and_balance_ok(prop).err(),
+            point: plane.calc_weight_and_balance(prop),
+            landing_point: plane.calc_landing_weight_and_balance(prop),
+            revision: plane.revision.clone(),
+        }
+    }
+
+    /// Same W&B result, whatever the revisions.
+    pub fn same_result(&self, other: &Outcome) -> bool {
+        self.take_off == other.take_off
+            && self.landing == other.landing
+            && self.point == other.point
+            && self.landing_point == other.landing_point
+    }
+}
+
+/// A calculated loading, stored as one line of the history file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub name: String,
+    pub values: HashMap<Kind, f64>,
+    #[serde(default)]
+    pub footprints: HashMap<Kind, f64>,
+    pub mass_table: Option<String>,
+    /// Revision of the plane's config the loading was calculated with.
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// `config_hash` of the config file the loading was calculated with.
+    pub config_hash: String,
+    /// `PlaneData::content_hash` of the plane's config the loading was
+    /// calculated with, missing in records from before it was added.
+    #[serde(default)]
+    pub plane_hash: Option<String>,
+    pub outcome: Outcome,
+}
+
+impl Record {
+    /// Date and time of the record in UTC, e.g. `2024-05-01 14:30`.
+    pub fn time(&self) -> String {
+        let days = self.timestamp / 86_400;
+        let minutes = self.timestamp % 86_400 / 60;
+        let (year, month, day) = civil_from_days(days as i64);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before the Unix epoch")
+        .as_secs()
+}
+
+pub fn append_record(path: &str, record: &Record) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+}
+
+/// All records in the history file, oldest first. A missing file is an empty
+/// history, a line that can't be read is an error with its line number.
+pub fn read_history(path: &str) -> Result<Vec<Record>, String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.map_err(|e| format!("History not readable on line {}: {}", index + 1, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid format of history on line {}: {}", index + 1, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Calculates a recorded loading again with the current config of the plane.
+/// Stations added to the plane since use the default loading, stations
+/// removed since are an error.
+pub fn verify(record: &Record, plane: &PlaneData) -> Result<Outcome, String> {
+    let values = fill_stations(plane, record.values.clone())?;
+    let prop = plane.properties(&values, record.footprints.clone())?;
+    Ok(Outcome::calc(plane, &prop))
+}
+
+// Howard Hinnant's days_from_civil inverted, days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_hash;
+
+    fn record(timestamp: u64) -> Record {
+        Record {
+            timestamp,
+            name: "SEMOA".to_string(),
+            values: HashMap::from([(Kind::Base, 450.0), (Kind::Pilot, 80.0)]),
+            footprints: HashMap::new(),
+            mass_table: None,
+            revision: Some("3".to_string()),
+            config_hash: config_hash(b"[]"),
+            plane_hash: Some(config_hash(b"{}")),
+            outcome: Outcome {
+                take_off: None,
+                landing: Some(FailReason::MaxLandingWeight),
+                point: WeightLever::new(530.0, 180.0),
+                landing_point: WeightLever::new(530.0, 180.0),
+                revision: Some("3".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn record_time() {
+        assert_eq!(record(0).time(), "1970-01-01 00:00");
+        assert_eq!(record(1_709_210_096).time(), "2024-02-29 12:34");
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let record = record(1_700_000_000);
+        let line = serde_json::to_string(&record).unwrap();
+
+        assert_eq!(serde_json::from_str::<Record>(&line).unwrap(), record);
+    }
+
+    #[test]
+    fn record_before_revisions() {
+        let line = r#"{"timestamp":0,"name":"SEMOA","values":{"base":450.0},"mass_table":null,"config_hash":"cbf29ce484222325","outcome":{"take_off":null,"landing":null,"point":{"weight":450.0,"lever":180.0},"landing_point":{"weight":450.0,"lever":180.0}}}"#;
+        let record = serde_json::from_str::<Record>(line).unwrap();
+
+        assert_eq!(record.config_hash, "cbf29ce484222325");
+        assert_eq!(record.plane_hash, None);
+        assert_eq!(record.revision, None);
+        assert_eq!(record.outcome.revision, None);
+    }
+
+    #[test]
+    fn read_history_reports_line() {
+        let path = std::env::temp_dir().join(format!("wbl-history-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let line = serde_json::to_string(&record(0)).unwrap();
+
+        assert_eq!(read_history(path), Ok(Vec::new()));
+        std::fs::write(path, format!("{}\n\n{}\n", line, line)).unwrap();
+        assert_eq!(read_history(path), Ok(vec![record(0), record(0)]));
+        std::fs::write(path, format!("{}\n\n{{\"timestamp\":\n", line)).unwrap();
+        let error = read_history(path).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(
+            error.starts_with("Invalid format of history on line 3:"),
+            "{}",
+            error
+        );
+    }
+}
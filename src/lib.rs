@@ -1,12 +1,24 @@
 use core::fmt;
 use std::{collections::HashMap, fs::File, io::BufReader, str::FromStr};
 
-use num::complex::ComplexFloat;
-use planes::{Input, PlaneData};
+use planes::{validate_planes, Input, PlaneData};
 use serde::{Deserialize, Serialize};
 
 pub mod calc_wb;
+pub mod glider;
+pub mod history;
+pub mod metrics;
 pub mod planes;
+pub mod profiles;
+pub mod protocol;
+pub mod revisions;
+pub mod seating;
+pub mod service;
+pub mod session;
+pub mod solver;
+pub mod standard_masses;
+pub mod stations;
+pub mod tls;
 
 #[derive(
     Default, PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord, Deserialize, Serialize,
@@ -14,20 +26,35 @@ pub mod planes;
 pub enum Kind {
     #[default]
     NoValue,
+    #[serde(rename = "base")]
     Base,
+    #[serde(rename = "fuel")]
     Fuel,
+    #[serde(rename = "bagage")]
     Bagage,
+    #[serde(rename = "bagage_front")]
     BagageFront,
+    #[serde(rename = "bagage_back")]
     BagageBack,
+    #[serde(rename = "bagage_wings")]
     BagageWings,
+    #[serde(rename = "pilot")]
     Pilot,
+    #[serde(rename = "co_pilot")]
     CoPilot,
+    #[serde(rename = "passenger_left")]
     PaxLeftBack,
+    #[serde(rename = "passenger_right")]
     PaxRightBack,
+    #[serde(rename = "trip_fuel")]
     TripFuel,
+    #[serde(rename = "water_ballast")]
+    WaterBallast,
+    #[serde(rename = "tail_ballast")]
+    TailBallast,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum FailReason {
     Bagage,
     BagageFront,
@@ -38,7 +65,44 @@ pub enum FailReason {
     Fuel,
     ZeroFuel,
     LandingFuel,
+    MaxLandingWeight,
+    /// Names of all the exceeded combined limits.
+    CombinedLimit(Vec<String>),
+    MinWeight(Kind),
+    FloorLoad(Kind),
     TorqueOutOfBounds,
+    LateralOutOfBounds,
+    MinCockpitLoad,
+    MaxCockpitLoad,
+    WaterBallast,
+    TailBallast,
+}
+
+impl FailReason {
+    /// Name of the variant without its data, e.g. `MinWeight`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailReason::Bagage => "Bagage",
+            FailReason::BagageFront => "BagageFront",
+            FailReason::BagageBack => "BagageBack",
+            FailReason::BagageWings => "BagageWings",
+            FailReason::MaxTakeOffWeight => "MaxTakeOffWeight",
+            FailReason::MaxWingLoad => "MaxWingLoad",
+            FailReason::Fuel => "Fuel",
+            FailReason::ZeroFuel => "ZeroFuel",
+            FailReason::LandingFuel => "LandingFuel",
+            FailReason::MaxLandingWeight => "MaxLandingWeight",
+            FailReason::CombinedLimit(_) => "CombinedLimit",
+            FailReason::MinWeight(_) => "MinWeight",
+            FailReason::FloorLoad(_) => "FloorLoad",
+            FailReason::TorqueOutOfBounds => "TorqueOutOfBounds",
+            FailReason::LateralOutOfBounds => "LateralOutOfBounds",
+            FailReason::MinCockpitLoad => "MinCockpitLoad",
+            FailReason::MaxCockpitLoad => "MaxCockpitLoad",
+            FailReason::WaterBallast => "WaterBallast",
+            FailReason::TailBallast => "TailBallast",
+        }
+    }
 }
 
 impl FromStr for Kind {
@@ -58,6 +122,8 @@ impl FromStr for Kind {
             "passenger_left" => Ok(Kind::PaxLeftBack),
             "passenger_right" => Ok(Kind::PaxRightBack),
             "trip_fuel" => Ok(Kind::TripFuel),
+            "water_ballast" => Ok(Kind::WaterBallast),
+            "tail_ballast" => Ok(Kind::TailBallast),
             _ => Err(format!("Invalid value of string: {}", s)),
         }
     }
@@ -66,7 +132,7 @@ impl FromStr for Kind {
 fn is_value_within_weight_limit(
     properties: &std::collections::HashMap<Kind, WeightLever>,
     kind: Kind,
-    limit: f32,
+    limit: f64,
 ) -> bool {
     let mut is_item_within_limit = true;
     if let Some(item) = properties.get(&kind) {
@@ -77,8 +143,8 @@ fn is_value_within_weight_limit(
 
 #[derive(PartialEq, PartialOrd, Debug, Clone, Deserialize, Serialize, Copy)]
 pub struct WeightLever {
-    pub weight: f32,
-    pub lever: f32,
+    pub weight: f64,
+    pub lever: f64,
 }
 
 impl std::default::Default for WeightLever {
@@ -87,27 +153,45 @@ impl std::default::Default for WeightLever {
     }
 }
 
+impl fmt::Display for WeightLever {
+    /// Uses the precision of the formatter, e.g. `{:.2}`, defaulting to one decimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(1);
+        write!(
+            f,
+            "weight: {:.*}, lever: {:.*}",
+            precision, self.weight, precision, self.lever
+        )
+    }
+}
+
 impl WeightLever {
-    pub fn new(weight: f32, lever: f32) -> Self {
+    pub fn new(weight: f64, lever: f64) -> Self {
         WeightLever { weight, lever }
     }
 
-    pub fn torque(&self) -> f32 {
+    pub fn torque(&self) -> f64 {
         self.lever * self.weight
     }
 }
 
-//ref: https://www.linkedin.com/pulse/short-formula-check-given-point-lies-inside-outside-polygon-ziemecki/
+/// Points closer than this to an edge of a polygon, in the units of its
+/// vertices, are on the border.
+pub const BORDER_EPSILON: f64 = 1e-3;
+
+/// Winding number test of whether `point` is inside the polygon. Points on the
+/// border, within `BORDER_EPSILON`, are inside only if `valid_border` is set.
+/// Works for concave polygons and for degenerate edges such as repeated vertices.
 pub fn is_inside_polygon(
     point: WeightLever,
-    vertices: &[WeightLever; 6],
+    vertices: &[WeightLever],
     valid_border: bool,
 ) -> Result<(), FailReason> {
-    let mut sum = num::complex::Complex::new(0.0, 0.0);
+    let mut winding_number = 0;
 
-    for i in 1..vertices.len() + 1 {
-        let v0 = &vertices[i - 1];
-        let v1 = &vertices[i % vertices.len()];
+    for i in 0..vertices.len() {
+        let v0 = &vertices[i];
+        let v1 = &vertices[(i + 1) % vertices.len()];
 
         if is_point_in_segment(&point, v0, v1) {
             if valid_border {
@@ -116,28 +200,159 @@ pub fn is_inside_polygon(
                 return Err(FailReason::TorqueOutOfBounds);
             }
         }
-        let v1_c = num::complex::Complex::new(v1.lever, v1.weight);
-        let p_c = num::complex::Complex::new(point.lever, point.weight);
-        let v0_c = num::complex::Complex::new(v0.lever, v0.weight);
-        sum += num::complex::Complex::ln((v1_c - p_c) / (v0_c - p_c));
+        // Lever is the x-axis and weight the y-axis. An upward crossing with the
+        // point to the left counts +1 and a downward one with it to the right -1.
+        let side = (v1.lever - v0.lever) * (point.weight - v0.weight)
+            - (point.lever - v0.lever) * (v1.weight - v0.weight);
+        if v0.weight <= point.weight {
+            if v1.weight > point.weight && side > 0.0 {
+                winding_number += 1;
+            }
+        } else if v1.weight <= point.weight && side < 0.0 {
+            winding_number -= 1;
+        }
     }
 
-    if sum.abs() <= 1.0 {
+    if winding_number == 0 {
         return Err(FailReason::TorqueOutOfBounds);
     }
     Ok(())
 }
 
+/// Distances from a point to the border of an envelope. All distances are
+/// positive inside the envelope and negative outside of it.
+#[derive(PartialEq, Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct EnvelopeMargin {
+    /// Shortest distance to the border.
+    pub distance: f64,
+    /// Nearest edge, going from vertex `edge` to vertex `edge + 1`.
+    pub edge: usize,
+    /// Distance to the border at the same weight, `None` if the weight is
+    /// outside of the envelope's weight range.
+    pub lever: Option<f64>,
+    /// Distance to the border at the same lever, `None` if the lever is
+    /// outside of the envelope's lever range.
+    pub weight: Option<f64>,
+}
+
+impl fmt::Display for EnvelopeMargin {
+    /// Uses the precision of the formatter like `WeightLever`, missing margins are `-`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(1);
+        let optional = |value: Option<f64>| {
+            value
+                .map(|value| format!("{:.*}", precision, value))
+                .unwrap_or("-".to_string())
+        };
+        write!(
+            f,
+            "distance: {:.*} to edge {}, lever: {}, weight: {}",
+            precision,
+            self.distance,
+            self.edge,
+            optional(self.lever),
+            optional(self.weight)
+        )
+    }
+}
+
+pub fn envelope_margin(point: WeightLever, vertices: &[WeightLever]) -> EnvelopeMargin {
+    let sign = if is_inside_polygon(point, vertices, true).is_ok() {
+        1.0
+    } else {
+        -1.0
+    };
+    let edges =
+        || (0..vertices.len()).map(|i| (i, &vertices[i], &vertices[(i + 1) % vertices.len()]));
+
+    let (edge, distance) = edges()
+        .map(|(i, v0, v1)| (i, distance_to_segment(&point, v0, v1)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("Envelope has vertices");
+    let lever = edges()
+        .filter(|(_, v0, v1)| {
+            v0.weight != v1.weight
+                && point.weight >= v0.weight.min(v1.weight)
+                && point.weight <= v0.weight.max(v1.weight)
+        })
+        .map(|(_, v0, v1)| {
+            let crossing = v0.lever
+                + (point.weight - v0.weight) / (v1.weight - v0.weight) * (v1.lever - v0.lever);
+            (crossing - point.lever).abs()
+        })
+        .min_by(f64::total_cmp);
+    let weight = weight_margin_along(point, vertices, 0.0);
+
+    EnvelopeMargin {
+        distance: sign * distance,
+        edge,
+        lever: lever.map(|lever| sign * lever),
+        weight: weight.map(|weight| sign * weight),
+    }
+}
+
+/// Weight distance from `point` to the border of the envelope along the line
+/// through the point on which the lever changes by `slope` per unit of weight,
+/// `None` if the line does not cross the border.
+pub(crate) fn weight_margin_along(
+    point: WeightLever,
+    vertices: &[WeightLever],
+    slope: f64,
+) -> Option<f64> {
+    (0..vertices.len())
+        .filter_map(|i| {
+            let (v0, v1) = (&vertices[i], &vertices[(i + 1) % vertices.len()]);
+            let (dl, dw) = (v1.lever - v0.lever, v1.weight - v0.weight);
+            let denominator = dl - slope * dw;
+            if denominator == 0.0 {
+                return None;
+            }
+            let t = (point.lever - v0.lever + slope * (v0.weight - point.weight)) / denominator;
+            (0.0..=1.0)
+                .contains(&t)
+                .then(|| (v0.weight + t * dw - point.weight).abs())
+        })
+        .min_by(f64::total_cmp)
+}
+
+fn distance_to_segment(p: &WeightLever, p0: &WeightLever, p1: &WeightLever) -> f64 {
+    let (dx, dy) = (p1.lever - p0.lever, p1.weight - p0.weight);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p.lever - p0.lever) * dx + (p.weight - p0.weight) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (closest_lever, closest_weight) = (p0.lever + t * dx, p0.weight + t * dy);
+    ((p.lever - closest_lever).powi(2) + (p.weight - closest_weight).powi(2)).sqrt()
+}
+
 fn is_point_in_segment(p: &WeightLever, p0: &WeightLever, p1: &WeightLever) -> bool {
-    let p0 = WeightLever::new(p0.weight - p.weight, p0.lever - p.lever);
-    let p1 = WeightLever::new(p1.weight - p.weight, p1.lever - p.lever);
+    distance_to_segment(p, p0, p1) <= BORDER_EPSILON
+}
 
-    let det = p0.weight * p1.lever - p1.weight * p0.lever;
-    let prod = p0.weight * p1.weight + p0.lever * p1.lever;
+/// 64 bit FNV-1a hash of config contents, in hex.
+pub fn config_hash(contents: &[u8]) -> String {
+    let hash = contents
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
 
-    (det == 0.0 && prod < 0.0)
-        || (p0.weight == 0.0 && p0.lever == 0.0)
-        || (p1.weight == 0.0 && p1.lever == 0.0)
+/// Parses a config with one or more lists of planes, like
+/// `read_plane_config_from_json`, and validates it.
+pub fn parse_plane_config(contents: &str) -> Result<Vec<PlaneData>, String> {
+    let planes = serde_json::Deserializer::from_str(contents)
+        .into_iter::<Vec<PlaneData>>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid format of config: {}", e))?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    validate_planes(&planes)?;
+    Ok(planes)
 }
 
 pub fn read_plane_config_from_json(path: &str) -> Vec<PlaneData> {
@@ -156,32 +371,239 @@ pub fn read_plane_config_from_json(path: &str) -> Vec<PlaneData> {
     planes_vec
 }
 
-pub fn iterate_maps<'a: 'b, 'b, K: Eq + std::hash::Hash + fmt::Debug, V>(
-    m1: &'a HashMap<K, V>,
-    m2: &'b HashMap<K, V>,
-) -> impl Iterator<Item = (&'a K, &'a V, &'b V)> {
-    m1.iter().map(move |(k, v1)| {
-        (
-            k,
-            v1,
-            m2.get(k)
-                .unwrap_or_else(|| panic!("{}", format!("Expected key: {:?}", &k))),
-        )
-    })
-}
-
 pub fn parse_name_from_input(input: &Input) -> String {
     input.name.to_string().trim_matches('\"').to_string()
 }
 
-pub fn parse_values_from_input(input: &Input) -> HashMap<Kind, f32> {
-    let mut weights = HashMap::new();
-    for key in input.values.keys() {
-        let v = input.values.get(key).unwrap();
-        weights.insert(
-            Kind::from_str(key).expect("Incorrect format in json values"),
-            v.as_f64().expect("Expected float") as f32,
+/// Weights in kg by station, once litres, profiles and passenger categories
+/// are resolved.
+pub fn parse_values_from_input(input: &Input) -> Result<HashMap<Kind, f64>, String> {
+    input
+        .values
+        .iter()
+        .map(|(key, value)| {
+            let kind = Kind::from_str(key)?;
+            let weight = value
+                .as_f64()
+                .ok_or_else(|| format!("Expected a weight in kg for {}: {}", key, value))?;
+            Ok((kind, weight))
+        })
+        .collect()
+}
+
+/// Footprints in m² by station. Every footprint must be positive.
+pub fn parse_footprints_from_input(input: &Input) -> Result<HashMap<Kind, f64>, String> {
+    input
+        .footprints
+        .iter()
+        .map(|(key, footprint)| {
+            let kind = Kind::from_str(key)?;
+            if *footprint <= 0.0 || footprint.is_nan() {
+                return Err(format!("Footprint of {} must be positive", key));
+            }
+            Ok((kind, *footprint))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    // Concave L-shape, lever along x and weight along y.
+    fn l_shape() -> Vec<WeightLever> {
+        [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 4.0),
+            (4.0, 4.0),
+            (4.0, 10.0),
+            (0.0, 10.0),
+        ]
+        .iter()
+        .map(|(lever, weight)| WeightLever::new(*weight, *lever))
+        .collect()
+    }
+
+    #[test]
+    fn config_hash_of_contents() {
+        assert_eq!(config_hash(b""), "cbf29ce484222325");
+        assert_ne!(config_hash(b"[1]"), config_hash(b"[2]"));
+    }
+
+    fn is_inside_l_shape(point: &WeightLever) -> bool {
+        let (x, y) = (point.lever, point.weight);
+        (x > 0.0 && x < 10.0 && y > 0.0 && y < 4.0) || (x > 0.0 && x < 4.0 && y > 0.0 && y < 10.0)
+    }
+
+    fn is_near_l_shape_border(point: &WeightLever) -> bool {
+        let vertices = l_shape();
+        (0..vertices.len()).any(|i| {
+            distance_to_segment(point, &vertices[i], &vertices[(i + 1) % vertices.len()])
+                <= 2.0 * BORDER_EPSILON
+        })
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        let planes = parse_plane_config(include_str!("input/config.json")).unwrap();
+
+        assert!(planes.iter().any(|plane| plane.name == "SEMOA"));
+        assert!(parse_plane_config("[]").is_err());
+        assert!(parse_plane_config("[{\"name\": \"SEMOA\"}]").is_err());
+    }
+
+    #[test]
+    fn footprints_positive() {
+        let mut input = Input::default();
+        input.footprints.insert("bagage".to_string(), 0.25);
+        assert_eq!(
+            parse_footprints_from_input(&input).unwrap(),
+            HashMap::from([(Kind::Bagage, 0.25)])
+        );
+
+        for footprint in [0.0, -0.25, f64::NAN] {
+            input.footprints.insert("bagage".to_string(), footprint);
+            assert!(parse_footprints_from_input(&input).is_err());
+        }
+    }
+
+    #[test]
+    fn values_must_be_weights() {
+        let mut input = Input::default();
+        input.values.insert("pilot".to_string(), 82.into());
+        assert_eq!(
+            parse_values_from_input(&input),
+            Ok(HashMap::from([(Kind::Pilot, 82.0)]))
+        );
+
+        input.values.insert("pilot".to_string(), true.into());
+        assert_eq!(
+            parse_values_from_input(&input),
+            Err("Expected a weight in kg for pilot: true".to_string())
+        );
+        input.values.clear();
+        input.values.insert("captain".to_string(), 82.into());
+        assert!(parse_values_from_input(&input).is_err());
+    }
+
+    #[test]
+    fn margin_inside() {
+        // Closest to the edge from (4, 4) to (4, 10) of the L-shape.
+        let margin = envelope_margin(WeightLever::new(6.0, 3.0), &l_shape());
+
+        assert_eq!(margin.distance, 1.0);
+        assert_eq!(margin.edge, 3);
+        assert_eq!(margin.lever, Some(1.0));
+        assert_eq!(margin.weight, Some(4.0));
+    }
+
+    #[test]
+    fn margin_outside_negative() {
+        let margin = envelope_margin(WeightLever::new(12.0, 2.0), &l_shape());
+
+        assert_eq!(margin.distance, -2.0);
+        assert_eq!(margin.edge, 4);
+        assert_eq!(margin.lever, None);
+        assert_eq!(margin.weight, Some(-2.0));
+    }
+
+    #[test]
+    fn display_precision() {
+        let point = WeightLever::new(612.345, 241.35);
+        let margin = EnvelopeMargin {
+            distance: 1.25,
+            edge: 2,
+            lever: Some(-0.5),
+            weight: None,
+        };
+
+        assert_eq!(point.to_string(), "weight: 612.3, lever: 241.3");
+        assert_eq!(format!("{:.2}", point), "weight: 612.35, lever: 241.35");
+        assert_eq!(format!("{:.0}", point), "weight: 612, lever: 241");
+        assert_eq!(
+            format!("{:.2}", margin),
+            "distance: 1.25 to edge 2, lever: -0.50, weight: -"
         );
     }
-    weights
+
+    #[test]
+    fn border_within_epsilon() {
+        let near = WeightLever::new(2.0, 10.0 - BORDER_EPSILON / 2.0);
+        let inside = WeightLever::new(2.0, 10.0 - 2.0 * BORDER_EPSILON);
+
+        assert!(is_inside_polygon(near, &l_shape(), true).is_ok());
+        assert!(is_inside_polygon(near, &l_shape(), false).is_err());
+        assert!(is_inside_polygon(inside, &l_shape(), false).is_ok());
+    }
+
+    #[test]
+    fn border_point_valid() {
+        let point = WeightLever::new(2.0, 10.0);
+
+        assert!(is_inside_polygon(point, &l_shape(), true).is_ok());
+        assert_eq!(
+            is_inside_polygon(point, &l_shape(), false).unwrap_err(),
+            FailReason::TorqueOutOfBounds
+        );
+    }
+
+    #[test]
+    fn concave_notch_outside() {
+        let point = WeightLever::new(7.0, 7.0);
+
+        assert!(is_inside_polygon(point, &l_shape(), true).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn matches_l_shape(lever in -2.0_f64..12.0, weight in -2.0_f64..12.0) {
+            let point = WeightLever::new(weight, lever);
+            prop_assume!(!is_near_l_shape_border(&point));
+
+            prop_assert_eq!(
+                is_inside_polygon(point, &l_shape(), false).is_ok(),
+                is_inside_l_shape(&point)
+            );
+        }
+
+        #[test]
+        fn reversed_order(lever in -2.0_f64..12.0, weight in -2.0_f64..12.0) {
+            let point = WeightLever::new(weight, lever);
+            let mut reversed = l_shape();
+            reversed.reverse();
+
+            prop_assert_eq!(
+                is_inside_polygon(point, &l_shape(), false).is_ok(),
+                is_inside_polygon(point, &reversed, false).is_ok()
+            );
+        }
+
+        #[test]
+        fn repeated_vertex(lever in -2.0_f64..12.0, weight in -2.0_f64..12.0, i in 0_usize..6) {
+            let point = WeightLever::new(weight, lever);
+            let mut degenerate = l_shape();
+            degenerate.insert(i, degenerate[i]);
+
+            prop_assert_eq!(
+                is_inside_polygon(point, &l_shape(), false).is_ok(),
+                is_inside_polygon(point, &degenerate, false).is_ok()
+            );
+        }
+
+        #[test]
+        fn points_on_edges(t in 0.0_f64..=1.0, i in 0_usize..6) {
+            let vertices = l_shape();
+            let (v0, v1) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            let point = WeightLever::new(
+                v0.weight + t * (v1.weight - v0.weight),
+                v0.lever + t * (v1.lever - v0.lever),
+            );
+
+            prop_assert!(is_inside_polygon(point, &vertices, true).is_ok());
+            prop_assert!(is_inside_polygon(point, &vertices, false).is_err());
+        }
+    }
 }
@@ -1,73 +1,568 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 use std::error::Error;
+use std::fs::{self, File};
 use std::io::BufReader;
-use std::{collections::HashMap, fs::File};
 use wbl::calc_wb::WeightAndBalance;
-use wbl::planes::{Input, ParsedInput, PlaneData, PlaneProperties};
-use wbl::{iterate_maps, parse_name_from_input, parse_values_from_input, read_plane_config_from_json, WeightLever};
+use wbl::glider::{recommend_ballast, wing_loading};
+use wbl::history::{append_record, now, read_history, verify, Outcome, Record};
+use wbl::planes::{Input, ParsedInput, PlaneData};
+use wbl::profiles::{read_profiles, resolve_profiles, write_profiles, Profile};
+use wbl::revisions::diff_planes;
+use wbl::seating::assign_seats;
+use wbl::solver::{ballast_for_cg, min_trip_fuel_for_landing};
+use wbl::standard_masses::{read_mass_tables, resolve_standard_masses};
+use wbl::stations::{fill_stations, parse_station_args, resolve_litres};
+use wbl::{
+    config_hash, parse_footprints_from_input, parse_name_from_input, parse_plane_config,
+    parse_values_from_input, EnvelopeMargin, FailReason,
+};
 
-fn parse_input_file(path: &str) -> ParsedInput {
+mod gui;
+
+fn read_input_file(path: &str) -> Input {
     let file = File::open(path).expect("Input file not found");
     let reader = BufReader::new(file);
-    let input: Input = serde_json::from_reader(reader).expect("Invalid format of input file");
+    serde_json::from_reader(reader).expect("Invalid format of input file")
+}
+
+fn parse_input(mut input: Input, planes: &[PlaneData], args: &Args) -> ParsedInput {
+    if args.mass_table.is_some() {
+        input.mass_table = args.mass_table.clone();
+    }
+    let name = parse_name_from_input(&input);
+    let plane = planes
+        .iter()
+        .find(|plane| plane.name == name)
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("Plane missing in config: {}", name),
+                )
+                .exit()
+        });
+    let (mass_table, values, footprints) = resolve_litres(&mut input, plane)
+        .and_then(|_| resolve_profiles(&mut input, &read_profiles(&args.profiles)))
+        .and_then(|_| resolve_standard_masses(&mut input, &read_mass_tables(&args.mass_tables)))
+        .and_then(|mass_table| {
+            let values = fill_stations(plane, parse_values_from_input(&input)?)?;
+            Ok((mass_table, values, parse_footprints_from_input(&input)?))
+        })
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit());
 
     ParsedInput {
         name: parse_name_from_input(&input),
-        values: parse_values_from_input(&input),
+        values,
+        footprints,
+        people: input.people,
+        mass_table,
     }
 }
 
+const CONFIG_PATH: &str = "./src/input/config.json";
+
+/// Planes of a config, validated like the servers do. Exits with the reason
+/// if the config can't be used.
+fn read_config(path: &str) -> Vec<PlaneData> {
+    fs::read_to_string(path)
+        .map_err(|e| format!("Could not read config {}: {}", path, e))
+        .and_then(|contents| parse_plane_config(&contents))
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit())
+}
+
 #[derive(Parser, Debug)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Input file with the loading
     #[arg(short, long)]
-    path: String,
+    path: Option<String>,
+    /// Warn when the lever margin to the envelope is below this value
+    #[arg(long, default_value_t = 1.0)]
+    margin_warning: f64,
+    /// Number of decimals in the output
+    #[arg(long, default_value_t = 1)]
+    precision: usize,
+    /// File with saved pilot and passenger profiles
+    #[arg(long, default_value = "./src/input/profiles.json")]
+    profiles: String,
+    /// File with custom standard mass tables
+    #[arg(long, default_value = "./src/input/mass_tables.json")]
+    mass_tables: String,
+    /// Standard mass table for passenger categories, overrides the input file
+    #[arg(long)]
+    mass_table: Option<String>,
+    /// Record the calculated loading in the history file
+    #[arg(long)]
+    record: bool,
+    /// File with the recorded loadings
+    #[arg(long, default_value = "./src/input/history.jsonl")]
+    history: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check a loading given as station flags, e.g. `check SEMOA --pilot 82 --fuel 60L`.
+    /// Stations that are not given use the plane's default loading.
+    Check {
+        name: String,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        stations: Vec<String>,
+    },
+    /// Edit the saved pilot and passenger profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Show what changed in the planes between two config files
+    Diff {
+        /// Config file of the old revision
+        old: String,
+        /// Config file of the new revision
+        #[arg(default_value = CONFIG_PATH)]
+        new: String,
+        /// Only compare this plane
+        #[arg(long)]
+        plane: Option<String>,
+    },
+    /// List, show and verify recorded loadings
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryAction {
+    /// List recorded loadings, optionally of one plane and date
+    List {
+        name: Option<String>,
+        /// Date as YYYY-MM-DD
+        #[arg(long)]
+        date: Option<String>,
+    },
+    /// Show a recorded loading by its number in the list
+    Show { id: usize },
+    /// Calculate a recorded loading again with the current config
+    Verify { id: usize },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileAction {
+    /// List all profiles
+    List,
+    /// Add or update a profile
+    Set {
+        name: String,
+        /// Body weight in kg
+        #[arg(long)]
+        weight: f64,
+        /// Typical baggage in kg
+        #[arg(long, default_value_t = 0.0)]
+        bagage: f64,
+    },
+    /// Remove a profile
+    Remove { name: String },
+    /// Set the clothing allowance added to everyone in a seat
+    ClothingAllowance { weight: f64 },
+    /// Edit the profiles in a window
+    Edit,
+}
+
+fn edit_profiles(path: &str, action: &ProfileAction) -> Result<(), Box<dyn Error>> {
+    let mut profiles = read_profiles(path);
+    match action {
+        ProfileAction::List => {
+            println!("Clothing allowance: {} kg", profiles.clothing_allowance);
+            for (name, profile) in &profiles.people {
+                println!(
+                    "@{}: {} kg, bagage {} kg",
+                    name, profile.weight, profile.bagage
+                );
+            }
+            return Ok(());
+        }
+        ProfileAction::Edit => {
+            gui::edit_profiles(path, profiles)?;
+            return Ok(());
+        }
+        ProfileAction::Set {
+            name,
+            weight,
+            bagage,
+        } => {
+            profiles.set(
+                name,
+                Profile {
+                    weight: *weight,
+                    bagage: *bagage,
+                },
+            )?;
+        }
+        ProfileAction::Remove { name } => {
+            profiles
+                .people
+                .remove(name)
+                .ok_or_else(|| format!("Unknown profile: {}", name))?;
+        }
+        ProfileAction::ClothingAllowance { weight } => profiles.clothing_allowance = *weight,
+    }
+    write_profiles(path, &profiles)?;
+    Ok(())
+}
+
+fn outcome_text(reason: &Option<FailReason>) -> String {
+    match reason {
+        Some(reason) => format!("failed for {:?}", reason),
+        None => "approved".to_string(),
+    }
+}
+
+fn show_history(args: &Args, action: &HistoryAction) -> Result<(), Box<dyn Error>> {
+    let history = read_history(&args.history)?;
+    let find = |id: usize| {
+        id.checked_sub(1)
+            .and_then(|index| history.get(index))
+            .ok_or_else(|| format!("No recorded loading #{}", id))
+    };
+    match action {
+        HistoryAction::List { name, date } => {
+            let records = history.iter().enumerate().filter(|(_, record)| {
+                name.as_ref().is_none_or(|name| &record.name == name)
+                    && date
+                        .as_ref()
+                        .is_none_or(|date| record.time().starts_with(date.as_str()))
+            });
+            for (index, record) in records {
+                println!(
+                    "#{} {} {}: take-off {}, landing {}",
+                    index + 1,
+                    record.time(),
+                    record.name,
+                    outcome_text(&record.outcome.take_off),
+                    outcome_text(&record.outcome.landing)
+                );
+            }
+        }
+        HistoryAction::Show { id } => {
+            println!("{}", serde_json::to_string_pretty(find(*id)?)?);
+        }
+        HistoryAction::Verify { id } => {
+            let record = find(*id)?;
+            let planes = read_config(CONFIG_PATH);
+            let plane = planes
+                .iter()
+                .find(|plane| plane.name == record.name)
+                .ok_or_else(|| format!("Plane missing in config: {}", record.name))?;
+            let changed = match &record.plane_hash {
+                Some(hash) => plane.content_hash() != *hash,
+                None => config_hash(&fs::read(CONFIG_PATH)?) != record.config_hash,
+            };
+            if changed {
+                println!(
+                    "Plane: {} config has changed since {}, revision was {} and is {}",
+                    record.name,
+                    record.time(),
+                    record.revision.as_deref().unwrap_or("unknown"),
+                    plane.revision.as_deref().unwrap_or("unknown")
+                );
+            }
+            let outcome = verify(record, plane)?;
+            println!(
+                "Plane: {} loading #{} is now take-off {}, landing {}",
+                record.name,
+                id,
+                outcome_text(&outcome.take_off),
+                outcome_text(&outcome.landing)
+            );
+            if outcome.same_result(&record.outcome) {
+                println!("Plane: {} loading #{} is unchanged", record.name, id);
+            } else {
+                println!(
+                    "Plane: {} loading #{} has changed, W&B point was {:.p$} and is {:.p$}",
+                    record.name,
+                    id,
+                    record.outcome.point,
+                    outcome.point,
+                    p = args.precision
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn revision_text(plane: &PlaneData) -> String {
+    format!(
+        "revision {} effective {}",
+        plane.revision.as_deref().unwrap_or("unknown"),
+        plane.effective_date.as_deref().unwrap_or("unknown")
+    )
+}
+
+fn diff_configs(old: &str, new: &str, name: &Option<String>) {
+    let old = read_config(old);
+    let new = read_config(new);
+    let mut names = old
+        .iter()
+        .chain(new.iter())
+        .map(|plane| &plane.name)
+        .filter(|plane| name.as_ref().is_none_or(|name| name == *plane))
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        match (
+            old.iter().find(|plane| &plane.name == name),
+            new.iter().find(|plane| &plane.name == name),
+        ) {
+            (Some(old), Some(new)) => {
+                let changes = diff_planes(old, new);
+                if changes.is_empty() {
+                    println!("Plane: {} is unchanged", name);
+                    continue;
+                }
+                println!(
+                    "Plane: {} changed from {} to {}",
+                    name,
+                    revision_text(old),
+                    revision_text(new)
+                );
+                for change in changes {
+                    println!("  {}", change);
+                }
+            }
+            (None, Some(new)) => println!("Plane: {} added, {}", name, revision_text(new)),
+            (Some(old), None) => println!("Plane: {} removed, {}", name, revision_text(old)),
+            (None, None) => unreachable!("Names are taken from the configs"),
+        }
+    }
+}
+
+fn print_margin(name: &str, phase: &str, margin: EnvelopeMargin, args: &Args) {
+    println!(
+        "Plane: {} has {} envelope margin: {:.p$}",
+        name,
+        phase,
+        margin,
+        p = args.precision
+    );
+    if margin.lever.unwrap_or(margin.distance) < args.margin_warning {
+        println!(
+            "Plane: {} WARNING {} margin to envelope edge {} is below {}",
+            name, phase, margin.edge, args.margin_warning
+        );
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    let p = args.precision;
+
+    if let Some(Command::Profile { action }) = &args.command {
+        return edit_profiles(&args.profiles, action);
+    }
+    if let Some(Command::History { action }) = &args.command {
+        return show_history(&args, action);
+    }
+    if let Some(Command::Diff { old, new, plane }) = &args.command {
+        diff_configs(old, new, plane);
+        return Ok(());
+    }
 
-    let planes = read_plane_config_from_json("./src/input/config.json");
-    let parsed_input = parse_input_file(&args.path);
+    let planes = read_config(CONFIG_PATH);
+    let parsed_input = match &args.command {
+        Some(Command::Check { name, stations }) => {
+            let plane = planes
+                .iter()
+                .find(|plane| &plane.name == name)
+                .unwrap_or_else(|| {
+                    Args::command()
+                        .error(
+                            ErrorKind::InvalidValue,
+                            format!("Plane missing in config: {}", name),
+                        )
+                        .exit()
+                });
+            let input = parse_station_args(plane, stations)
+                .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit());
+            parse_input(input, &planes, &args)
+        }
+        Some(Command::Profile { .. } | Command::History { .. } | Command::Diff { .. }) => {
+            unreachable!("Profiles, history and diff are handled above")
+        }
+        None => match &args.path {
+            Some(path) => parse_input(read_input_file(path), &planes, &args),
+            None => Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Either --path or a subcommand is required",
+                )
+                .exit(),
+        },
+    };
     let plane_config: &PlaneData = &planes[planes
         .iter()
         .position(|plane| plane.name == parsed_input.name)
         .expect("Plane missing in config")];
-    let plane_levers = plane_config.to_lever_map();
-    let plane_properties =
-        PlaneProperties::new(iterate_maps(&plane_levers, &parsed_input.values).fold(
-            HashMap::new(),
-            move |mut properties, (k, a, w)| {
-                properties.insert(*k, WeightLever::new(*w, *a));
-                properties
-            },
-        ));
+    let plane_properties = plane_config
+        .properties(&parsed_input.values, parsed_input.footprints.clone())
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit());
 
+    println!(
+        "Plane: {} uses config revision {}, effective {}, hash {}",
+        parsed_input.name,
+        plane_config.revision.as_deref().unwrap_or("unknown"),
+        plane_config.effective_date.as_deref().unwrap_or("unknown"),
+        plane_config.content_hash()
+    );
+    if let Some(mass_table) = &parsed_input.mass_table {
+        println!(
+            "Plane: {} is loaded with standard masses from table {}",
+            parsed_input.name, mass_table
+        );
+    }
     let wb = plane_config.is_weight_and_balance_ok(&plane_properties);
-    if wb.is_ok() {
-        println!("Plane: {} is approved for W&B", parsed_input.name);
-
+    if let Err(reason) = wb {
+        println!("Plane: {} failed W&B for: {:?}", parsed_input.name, reason);
+        if matches!(
+            reason,
+            FailReason::TorqueOutOfBounds | FailReason::ZeroFuel | FailReason::MinWeight(_)
+        ) {
+            if let Some(ballast) = ballast_for_cg(plane_config, &plane_properties) {
+                println!(
+                    "Plane: {} needs {:.p$} kg ballast at {:?}",
+                    parsed_input.name, ballast.weight, ballast.station
+                );
+            }
+        }
     } else {
-        println!("Plane: {} failed W&B for: {:?}", parsed_input.name, wb.unwrap_err());
+        println!("Plane: {} is approved for W&B", parsed_input.name);
     }
+    let wb_point = plane_config.calc_weight_and_balance(&plane_properties);
     println!(
-        "Plane: {} has W&B point at: {:?}",
-        parsed_input.name,
-        plane_config.calc_weight_and_balance(&plane_properties)
+        "Plane: {} has W&B point at: {:.p$}",
+        parsed_input.name, wb_point
+    );
+    print_margin(
+        &parsed_input.name,
+        "take-off",
+        plane_config.envelope_margin(wb_point)?,
+        &args,
     );
+    if let Some(percent_mac) = plane_config.percent_mac(&wb_point) {
+        println!(
+            "Plane: {} has CG at {:.p$} % MAC",
+            parsed_input.name, percent_mac
+        );
+    }
+    if plane_config.lateral_envelope.is_some() {
+        println!(
+            "Plane: {} has lateral W&B point at: {:.p$}",
+            parsed_input.name,
+            plane_config.calc_lateral_weight_and_balance(&plane_properties)
+        );
+    }
 
-    let wb_landing = plane_config.is_weight_and_balance_ok(&plane_properties);
-    if wb_landing.is_ok() {
-        println!("Plane: {} is approved for W&B when landing", parsed_input.name);
+    if let Some(wing_loading) = wing_loading(plane_config, &plane_properties) {
+        println!(
+            "Plane: {} has a wing loading of {:.p$} kg/m²",
+            parsed_input.name, wing_loading
+        );
+    }
+    if let Some(ballast) = recommend_ballast(plane_config, &plane_properties) {
+        println!(
+            "Plane: {} needs {:.p$} kg cockpit ballast and can take {:.p$} kg water ballast",
+            parsed_input.name, ballast.cockpit_ballast, ballast.max_water_ballast
+        );
+        if let Some(tail_ballast) = ballast.max_tail_ballast {
+            println!(
+                "Plane: {} can take {:.p$} kg tail ballast",
+                parsed_input.name, tail_ballast
+            );
+        }
+    }
+
+    if !parsed_input.people.is_empty() {
+        let assignments = assign_seats(plane_config, &plane_properties, &parsed_input.people);
+        if assignments.is_empty() {
+            println!(
+                "Plane: {} has no seating that passes W&B",
+                parsed_input.name
+            );
+        }
+        for assignment in assignments {
+            println!(
+                "Plane: {} seating {:?} has margin {:.p$}",
+                parsed_input.name, assignment.seats, assignment.margin
+            );
+        }
+    }
 
+    let wb_landing = plane_config.is_landing_weight_and_balance_ok(&plane_properties);
+    if let Err(reason) = wb_landing {
+        println!("Plane: {} failed landing W&B for: {:?}", parsed_input.name, reason);
+        if reason == FailReason::MaxLandingWeight {
+            match min_trip_fuel_for_landing(plane_config, &plane_properties) {
+                Some(trip_fuel) => println!(
+                    "Plane: {} needs at least {:.p$} kg trip fuel to land below MLW",
+                    parsed_input.name, trip_fuel
+                ),
+                None => println!(
+                    "Plane: {} cannot burn enough fuel to land below MLW",
+                    parsed_input.name
+                ),
+            }
+        }
     } else {
-        println!("Plane: {} failed landing W&B for: {:?}", parsed_input.name, wb_landing.unwrap_err());
+        println!("Plane: {} is approved for W&B when landing", parsed_input.name);
     }
 
+    let wb_landing_point = plane_config.calc_landing_weight_and_balance(&plane_properties);
     println!(
-        "Plane: {} has a landing W&B point at: {:?}",
-        parsed_input.name,
-        plane_config.calc_landing_weight_and_balance(&plane_properties)
+        "Plane: {} has a landing W&B point at: {:.p$}",
+        parsed_input.name, wb_landing_point
+    );
+    print_margin(
+        &parsed_input.name,
+        "landing",
+        plane_config.envelope_margin(wb_landing_point)?,
+        &args,
     );
+    if let Some(percent_mac) = plane_config.percent_mac(&wb_landing_point) {
+        println!(
+            "Plane: {} has landing CG at {:.p$} % MAC",
+            parsed_input.name, percent_mac
+        );
+    }
+    if plane_config.lateral_envelope.is_some() {
+        println!(
+            "Plane: {} has a landing lateral W&B point at: {:.p$}",
+            parsed_input.name,
+            plane_config.calc_landing_lateral_weight_and_balance(&plane_properties)
+        );
+    }
+
+    if args.record {
+        let record = Record {
+            timestamp: now(),
+            name: parsed_input.name.clone(),
+            values: parsed_input.values,
+            footprints: parsed_input.footprints,
+            mass_table: parsed_input.mass_table,
+            revision: plane_config.revision.clone(),
+            config_hash: config_hash(&fs::read(CONFIG_PATH)?),
+            plane_hash: Some(plane_config.content_hash()),
+            outcome: Outcome::calc(plane_config, &plane_properties),
+        };
+        append_record(&args.history, &record)?;
+        println!(
+            "Plane: {} loading is recorded in {}",
+            record.name, args.history
+        );
+    }
 
     // // /*
     // // TODO:
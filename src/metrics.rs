@@ -0,0 +1,196 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use crate::history::Outcome;
+
+/// Upper bounds in seconds of the buckets of the request latency histogram.
+pub const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5];
+
+/// Counters of the server, rendered in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics(Mutex<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    /// By request type and result, `ok` or `error`.
+    requests: BTreeMap<(String, &'static str), u64>,
+    /// By request type.
+    latency: BTreeMap<String, Histogram>,
+    /// By plane and W&B result, `pass` or `fail`.
+    checks: BTreeMap<(String, &'static str), u64>,
+    /// By phase, `take_off` or `landing`, and `FailReason` variant.
+    failures: BTreeMap<(&'static str, &'static str), u64>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Cumulative, requests at or below each bound of `LATENCY_BUCKETS`.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    pub fn request(&self, kind: &str, ok: bool, duration: Duration) {
+        let mut counts = self.0.lock().expect("Metrics poisoned");
+        let result = if ok { "ok" } else { "error" };
+        *counts
+            .requests
+            .entry((kind.to_string(), result))
+            .or_default() += 1;
+        let seconds = duration.as_secs_f64();
+        let histogram = counts.latency.entry(kind.to_string()).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    pub fn outcome(&self, plane: &str, outcome: &Outcome) {
+        let mut counts = self.0.lock().expect("Metrics poisoned");
+        let result = match outcome.take_off.is_none() && outcome.landing.is_none() {
+            true => "pass",
+            false => "fail",
+        };
+        *counts
+            .checks
+            .entry((plane.to_string(), result))
+            .or_default() += 1;
+        for (phase, reason) in [
+            ("take_off", &outcome.take_off),
+            ("landing", &outcome.landing),
+        ] {
+            if let Some(reason) = reason {
+                *counts.failures.entry((phase, reason.name())).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let counts = self.0.lock().expect("Metrics poisoned");
+        let mut out = String::new();
+        // Writing to a String does not fail.
+        let _ = write!(
+            out,
+            "# HELP wbl_requests_total Requests handled, by type and result.\n\
+             # TYPE wbl_requests_total counter\n"
+        );
+        for ((kind, result), count) in &counts.requests {
+            let _ = writeln!(
+                out,
+                "wbl_requests_total{{type=\"{}\",result=\"{}\"}} {}",
+                escape(kind),
+                result,
+                count
+            );
+        }
+        let _ = write!(
+            out,
+            "# HELP wbl_request_duration_seconds Time to handle a request, by type.\n\
+             # TYPE wbl_request_duration_seconds histogram\n"
+        );
+        for (kind, histogram) in &counts.latency {
+            let kind = escape(kind);
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "wbl_request_duration_seconds_bucket{{type=\"{}\",le=\"{}\"}} {}",
+                    kind, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "wbl_request_duration_seconds_bucket{{type=\"{}\",le=\"+Inf\"}} {}\n\
+                 wbl_request_duration_seconds_sum{{type=\"{}\"}} {}\n\
+                 wbl_request_duration_seconds_count{{type=\"{}\"}} {}",
+                kind, histogram.count, kind, histogram.sum, kind, histogram.count
+            );
+        }
+        let _ = write!(
+            out,
+            "# HELP wbl_checks_total W&B calculations, by plane and result.\n\
+             # TYPE wbl_checks_total counter\n"
+        );
+        for ((plane, result), count) in &counts.checks {
+            let _ = writeln!(
+                out,
+                "wbl_checks_total{{plane=\"{}\",result=\"{}\"}} {}",
+                escape(plane),
+                result,
+                count
+            );
+        }
+        let _ = write!(
+            out,
+            "# HELP wbl_failures_total Failed W&B calculations, by phase and reason.\n\
+             # TYPE wbl_failures_total counter\n"
+        );
+        for ((phase, reason), count) in &counts.failures {
+            let _ = writeln!(
+                out,
+                "wbl_failures_total{{phase=\"{}\",reason=\"{}\"}} {}",
+                phase, reason, count
+            );
+        }
+        out
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FailReason, Kind, WeightLever};
+
+    #[test]
+    fn render_counts() {
+        let metrics = Metrics::default();
+        let outcome = |take_off, landing| Outcome {
+            take_off,
+            landing,
+            point: WeightLever::default(),
+            landing_point: WeightLever::default(),
+            revision: None,
+        };
+
+        metrics.request("calculate", true, Duration::from_millis(3));
+        metrics.request("calculate", false, Duration::from_secs(1));
+        metrics.outcome("SEMOA", &outcome(None, None));
+        metrics.outcome(
+            "SE\"MOA",
+            &outcome(
+                Some(FailReason::MinWeight(Kind::Pilot)),
+                Some(FailReason::MaxLandingWeight),
+            ),
+        );
+        let text = metrics.render();
+
+        for line in [
+            "wbl_requests_total{type=\"calculate\",result=\"ok\"} 1",
+            "wbl_requests_total{type=\"calculate\",result=\"error\"} 1",
+            "wbl_request_duration_seconds_bucket{type=\"calculate\",le=\"0.0025\"} 0",
+            "wbl_request_duration_seconds_bucket{type=\"calculate\",le=\"0.005\"} 1",
+            "wbl_request_duration_seconds_bucket{type=\"calculate\",le=\"+Inf\"} 2",
+            "wbl_request_duration_seconds_count{type=\"calculate\"} 2",
+            "wbl_checks_total{plane=\"SEMOA\",result=\"pass\"} 1",
+            "wbl_checks_total{plane=\"SE\\\"MOA\",result=\"fail\"} 1",
+            "wbl_failures_total{phase=\"take_off\",reason=\"MinWeight\"} 1",
+            "wbl_failures_total{phase=\"landing\",reason=\"MaxLandingWeight\"} 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} missing in\n{}",
+                line,
+                text
+            );
+        }
+    }
+}
@@ -1,63 +1,288 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    calc_wb::WeightAndBalance, is_inside_polygon, is_value_within_weight_limit, FailReason, Kind,
-    WeightLever,
+    calc_wb::WeightAndBalance, config_hash, envelope_margin, glider::GliderData, is_inside_polygon,
+    is_value_within_weight_limit, seating::Person, stations::station_key, weight_margin_along,
+    EnvelopeMargin, FailReason, Kind, WeightLever,
 };
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+pub const DEFAULT_FUEL_DENSITY: f64 = 0.72;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Input {
     pub name: String,
     pub values: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub footprints: HashMap<String, f64>,
+    #[serde(default)]
+    pub people: Vec<Person>,
+    /// Standard mass table used for passenger categories, e.g. `"adult_male"`.
+    pub mass_table: Option<String>,
 }
 
 pub struct ParsedInput {
     pub name: String,
-    pub values: HashMap<Kind, f32>,
+    pub values: HashMap<Kind, f64>,
+    pub footprints: HashMap<Kind, f64>,
+    pub people: Vec<Person>,
+    /// Name of the standard mass table if standard masses were used.
+    pub mass_table: Option<String>,
+}
+
+/// Checks all planes of a config and that their names are unique.
+pub fn validate_planes(planes: &[PlaneData]) -> Result<(), String> {
+    if planes.is_empty() {
+        return Err("Config has no planes".to_string());
+    }
+    for (i, plane) in planes.iter().enumerate() {
+        plane
+            .validate()
+            .map_err(|e| format!("Plane {}: {}", plane.name, e))?;
+        if planes[..i].iter().any(|other| other.name == plane.name) {
+            return Err(format!("Plane {} is in the config twice", plane.name));
+        }
+    }
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Levers {
-    pub base: f32,
-    pub fuel: f32,
-    pub trip_fuel: f32,
-    pub bagage: Option<f32>,
-    pub bagage_back: Option<f32>,
-    pub bagage_front: Option<f32>,
-    pub bagage_wings: Option<f32>,
-    pub pilot: f32,
-    pub co_pilot: f32,
-    pub passenger_left: Option<f32>,
-    pub passenger_right: Option<f32>,
+    pub base: f64,
+    /// Required unless the plane is a glider.
+    pub fuel: Option<f64>,
+    /// Required unless the plane is a glider.
+    pub trip_fuel: Option<f64>,
+    pub bagage: Option<f64>,
+    pub bagage_back: Option<f64>,
+    pub bagage_front: Option<f64>,
+    pub bagage_wings: Option<f64>,
+    pub pilot: f64,
+    pub co_pilot: f64,
+    pub passenger_left: Option<f64>,
+    pub passenger_right: Option<f64>,
+    pub water_ballast: Option<f64>,
+    pub tail_ballast: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct MaxWeights {
-    pub max_take_off_weight: f32,
-    pub max_fuel_weight: f32,
-    pub max_zero_fuel_mass: Option<f32>,
-    pub max_bagage_weight: Option<f32>,
-    pub max_bagage_weight_front: Option<f32>,
-    pub max_bagage_weight_back: Option<f32>,
-    pub max_bagage_weight_wings: Option<f32>,
+    pub max_take_off_weight: f64,
+    #[serde(default)]
+    pub max_fuel_weight: f64,
+    pub max_zero_fuel_mass: Option<f64>,
+    pub max_landing_weight: Option<f64>,
+    pub max_bagage_weight: Option<f64>,
+    pub max_bagage_weight_front: Option<f64>,
+    pub max_bagage_weight_back: Option<f64>,
+    pub max_bagage_weight_wings: Option<f64>,
+}
+
+/// Structural limit on the summed weight of one or more stations,
+/// e.g. "baggage areas 1+2 max 54 kg".
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WeightLimit {
+    pub name: String,
+    pub stations: Vec<Kind>,
+    pub max_weight: f64,
+}
+
+impl WeightLimit {
+    fn is_exceeded(&self, prop: &PlaneProperties) -> bool {
+        prop.0
+            .iter()
+            .filter(|(k, _)| self.stations.contains(k))
+            .map(|(_, wb)| wb.weight)
+            .sum::<f64>()
+            > self.max_weight
+    }
+}
+
+/// Stations carried by the wings when `wing_load_stations` is not set.
+pub const DEFAULT_WING_LOAD_STATIONS: [Kind; 5] = [
+    Kind::Base,
+    Kind::Pilot,
+    Kind::CoPilot,
+    Kind::BagageBack,
+    Kind::BagageFront,
+];
+
+/// Cargo floor of a station. Area is in m² and floor load in kg/m².
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CargoArea {
+    pub station: Kind,
+    pub floor_area: f64,
+    pub max_floor_load: f64,
+}
+
+/// Mean aerodynamic chord, given by the lever of its leading edge and its length.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct Mac {
+    pub leading_edge: f64,
+    pub length: f64,
+}
+
+impl Mac {
+    pub fn percent(&self, lever: f64) -> f64 {
+        (lever - self.leading_edge) / self.length * 100.0
+    }
+
+    pub fn lever(&self, percent: f64) -> f64 {
+        self.leading_edge + percent / 100.0 * self.length
+    }
+}
+
+/// What the lever coordinate of an envelope vertex is. POHs often publish the
+/// envelope as weight against moment divided by some factor, e.g. moment/1000,
+/// or as weight against percent of MAC.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+pub enum EnvelopeAxis {
+    #[default]
+    #[serde(rename = "lever")]
+    Lever,
+    #[serde(rename = "moment")]
+    Moment(f64),
+    #[serde(rename = "percent_mac")]
+    PercentMac,
+}
+
+impl EnvelopeAxis {
+    /// Converts a weight and lever point into the space of the envelope.
+    pub fn project(&self, point: WeightLever, mac: Option<&Mac>) -> Result<WeightLever, String> {
+        Ok(match self {
+            EnvelopeAxis::Lever => point,
+            EnvelopeAxis::Moment(divisor) => {
+                WeightLever::new(point.weight, point.torque() / divisor)
+            }
+            EnvelopeAxis::PercentMac => {
+                WeightLever::new(point.weight, required_mac(mac)?.percent(point.lever))
+            }
+        })
+    }
+
+    /// How many envelope lever units one unit of lever is at the given weight.
+    pub fn lever_scale(&self, weight: f64, mac: Option<&Mac>) -> Result<f64, String> {
+        Ok(match self {
+            EnvelopeAxis::Lever => 1.0,
+            EnvelopeAxis::Moment(divisor) => weight / divisor,
+            EnvelopeAxis::PercentMac => 100.0 / required_mac(mac)?.length,
+        })
+    }
+}
+
+fn required_mac(mac: Option<&Mac>) -> Result<&Mac, String> {
+    mac.ok_or_else(|| "Envelope in percent of MAC but MAC missing".to_string())
+}
+
+/// What the first coordinate of a lateral envelope vertex is. The second
+/// coordinate is always the lateral arm.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+pub enum LateralAxis {
+    #[default]
+    #[serde(rename = "weight")]
+    Weight,
+    #[serde(rename = "longitudinal")]
+    Longitudinal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LateralEnvelope {
+    #[serde(default)]
+    pub axis: LateralAxis,
+    /// Lever or moment, the MAC is along the longitudinal axis.
+    #[serde(default, deserialize_with = "lateral_lever_axis")]
+    pub lever_axis: EnvelopeAxis,
+    pub vertices: [[f64; 2]; 6],
+}
+
+fn lateral_lever_axis<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<EnvelopeAxis, D::Error> {
+    match EnvelopeAxis::deserialize(deserializer)? {
+        EnvelopeAxis::PercentMac => Err(serde::de::Error::custom(
+            "Lateral envelope cannot be in percent of MAC",
+        )),
+        axis => Ok(axis),
+    }
+}
+
+/// Checks that points can be projected into the envelope and that it has an
+/// area, `name` starts the error.
+fn validate_envelope(
+    name: &str,
+    axis: &EnvelopeAxis,
+    vertices: &[[f64; 2]; 6],
+) -> Result<(), String> {
+    if let EnvelopeAxis::Moment(divisor) = axis {
+        if *divisor == 0.0 || !divisor.is_finite() {
+            return Err(format!(
+                "{} moment divisor must be a number other than 0",
+                name
+            ));
+        }
+    }
+    if vertices.iter().flatten().any(|value| !value.is_finite()) {
+        return Err(format!("{} vertices must be numbers", name));
+    }
+    let area = (0..vertices.len())
+        .map(|i| {
+            let [w0, l0] = vertices[i];
+            let [w1, l1] = vertices[(i + 1) % vertices.len()];
+            l0 * w1 - l1 * w0
+        })
+        .sum::<f64>();
+    if area == 0.0 {
+        return Err(format!("{} has no area", name));
+    }
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PlaneData {
     pub name: String,
+    /// Revision of the plane's data, e.g. the number of the W&B report.
+    pub revision: Option<String>,
+    /// Date from which the revision is valid, YYYY-MM-DD.
+    pub effective_date: Option<String>,
     pub levers: Levers,
     pub max_weights: MaxWeights,
-    pub vertices: [[f32; 2]; 6],
+    /// Fuel density in kg/L, AVGAS if not set.
+    pub fuel_density: Option<f64>,
+    /// Weights used for stations that are not given, e.g. the base weight.
+    #[serde(default)]
+    pub default_loading: HashMap<Kind, f64>,
+    /// Checked in the order of the stations, the first one below its
+    /// minimum is reported.
+    #[serde(default)]
+    pub min_weights: BTreeMap<Kind, f64>,
+    #[serde(default)]
+    pub limits: Vec<WeightLimit>,
+    /// Stations counted against `max_zero_fuel_mass`, `DEFAULT_WING_LOAD_STATIONS`
+    /// if not set.
+    pub wing_load_stations: Option<Vec<Kind>>,
+    #[serde(default)]
+    pub cargo_areas: Vec<CargoArea>,
+    #[serde(default)]
+    pub lateral_levers: HashMap<Kind, f64>,
+    pub lateral_envelope: Option<LateralEnvelope>,
+    pub mac: Option<Mac>,
+    pub glider: Option<GliderData>,
+    #[serde(default)]
+    pub envelope_axis: EnvelopeAxis,
+    pub vertices: [[f64; 2]; 6],
 }
 
 impl PlaneData {
-    pub fn to_lever_map(&self) -> HashMap<Kind, f32> {
+    pub fn to_lever_map(&self) -> HashMap<Kind, f64> {
         let mut map = HashMap::new();
         map.insert(Kind::Base, self.levers.base);
-        map.insert(Kind::Fuel, self.levers.fuel);
-        map.insert(Kind::TripFuel, self.levers.trip_fuel);
+        if let Some(value) = self.levers.fuel {
+            map.insert(Kind::Fuel, value);
+        }
+        if let Some(value) = self.levers.trip_fuel {
+            map.insert(Kind::TripFuel, value);
+        }
         if let Some(value) = self.levers.bagage_back {
             map.insert(Kind::BagageBack, value);
         }
@@ -80,10 +305,124 @@ impl PlaneData {
         if let Some(value) = self.levers.passenger_right {
             map.insert(Kind::PaxRightBack, value);
         }
+        if let Some(value) = self.levers.water_ballast {
+            map.insert(Kind::WaterBallast, value);
+        }
+        if let Some(value) = self.levers.tail_ballast {
+            map.insert(Kind::TailBallast, value);
+        }
 
         map
     }
 
+    /// Properties of the plane loaded with `values`, which must hold a weight
+    /// for every station of the plane, see `fill_stations`. The plane must
+    /// weigh something at take-off and landing.
+    pub fn properties(
+        &self,
+        values: &HashMap<Kind, f64>,
+        footprints: HashMap<Kind, f64>,
+    ) -> Result<PlaneProperties, String> {
+        let stations = self
+            .to_lever_map()
+            .into_iter()
+            .map(|(kind, lever)| {
+                let weight = values
+                    .get(&kind)
+                    .ok_or_else(|| format!("Missing weight for station: {}", station_key(kind)))?;
+                Ok((kind, WeightLever::new(*weight, lever)))
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        let properties = PlaneProperties::new(stations).with_footprints(footprints);
+        if properties.get_total_weights() <= 0.0 || properties.get_landing_weights() <= 0.0 {
+            return Err("Total weight must be positive at take-off and landing".to_string());
+        }
+        Ok(properties)
+    }
+
+    /// Hash of the plane's data used by the calculations, changes with any
+    /// edit of it in the config but not with edits of other planes or of the
+    /// revision and effective date. All other fields are hashed, also ones
+    /// added later.
+    pub fn content_hash(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("Plane data is serializable");
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("revision");
+            fields.remove("effective_date");
+        }
+        config_hash(value.to_string().as_bytes())
+    }
+
+    /// Checks that the plane's data can be used for calculations.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Plane without name".to_string());
+        }
+        if self.max_weights.max_take_off_weight <= 0.0 {
+            return Err("max_take_off_weight must be positive".to_string());
+        }
+        if self.fuel_density.is_some_and(|density| density <= 0.0) {
+            return Err("fuel_density must be positive".to_string());
+        }
+        if self
+            .cargo_areas
+            .iter()
+            .any(|area| area.floor_area <= 0.0 || area.floor_area.is_nan())
+        {
+            return Err("Cargo floor_area must be positive".to_string());
+        }
+        if self.glider.is_none() && (self.levers.fuel.is_none() || self.levers.trip_fuel.is_none())
+        {
+            return Err("Levers for fuel and trip_fuel are required".to_string());
+        }
+        let max_fuel = self.max_weights.max_fuel_weight;
+        if self.glider.is_none() && (max_fuel <= 0.0 || max_fuel.is_nan()) {
+            return Err("max_fuel_weight must be positive".to_string());
+        }
+        if self
+            .glider
+            .as_ref()
+            .is_some_and(|glider| glider.wing_area <= 0.0 || glider.wing_area.is_nan())
+        {
+            return Err("Glider wing_area must be positive".to_string());
+        }
+        if self.mac.is_some_and(|mac| {
+            mac.length <= 0.0 || !mac.length.is_finite() || !mac.leading_edge.is_finite()
+        }) {
+            return Err("MAC length must be positive".to_string());
+        }
+        if self.envelope_axis == EnvelopeAxis::PercentMac && self.mac.is_none() {
+            return Err("Envelope in percent of MAC but MAC missing".to_string());
+        }
+        validate_envelope("Envelope", &self.envelope_axis, &self.vertices)?;
+        if let Some(envelope) = &self.lateral_envelope {
+            if envelope.lever_axis == EnvelopeAxis::PercentMac {
+                return Err("Lateral envelope cannot be in percent of MAC".to_string());
+            }
+            validate_envelope("Lateral envelope", &envelope.lever_axis, &envelope.vertices)?;
+        }
+
+        let levers = self.to_lever_map();
+        let stations = self
+            .default_loading
+            .keys()
+            .chain(self.min_weights.keys())
+            .chain(self.limits.iter().flat_map(|limit| limit.stations.iter()))
+            .chain(self.wing_load_stations.iter().flatten())
+            .chain(self.cargo_areas.iter().map(|area| &area.station));
+        for station in stations {
+            if !levers.contains_key(station) {
+                return Err(format!("Station {:?} has no lever", station));
+            }
+        }
+        Ok(())
+    }
+
+    /// Lever of a W&B point in percent of MAC, if the plane has a MAC.
+    pub fn percent_mac(&self, point: &WeightLever) -> Option<f64> {
+        self.mac.map(|mac| mac.percent(point.lever))
+    }
+
     fn is_mtow_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
         if prop.get_total_weights() > self.max_weights.max_take_off_weight {
             return Err(FailReason::MaxTakeOffWeight);
@@ -91,6 +430,15 @@ impl PlaneData {
         Ok(())
     }
 
+    fn is_mlw_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        if let Some(max_landing_weight) = self.max_weights.max_landing_weight {
+            if prop.get_landing_weights() > max_landing_weight {
+                return Err(FailReason::MaxLandingWeight);
+            }
+        }
+        Ok(())
+    }
+
     fn flatten_vertices(&self) -> [WeightLever; 6] {
         self.vertices
             .iter()
@@ -100,16 +448,88 @@ impl PlaneData {
             .expect("Should be able to create array")
     }
 
+    fn get_lateral_torque(&self, prop: &PlaneProperties) -> f64 {
+        prop.0
+            .iter()
+            .filter(|(k, _)| **k != Kind::TripFuel)
+            .map(|(k, wb)| wb.weight * self.lateral_levers.get(k).copied().unwrap_or(0.0))
+            .sum()
+    }
+
+    fn get_landing_lateral_torque(&self, prop: &PlaneProperties) -> f64 {
+        let trip_fuel = prop.trip_fuel();
+        self.get_lateral_torque(prop)
+            - trip_fuel.weight
+                * self
+                    .lateral_levers
+                    .get(&Kind::TripFuel)
+                    .copied()
+                    .unwrap_or(0.0)
+    }
+
+    fn is_lateral_ok(
+        &self,
+        longitudinal: WeightLever,
+        lateral: WeightLever,
+    ) -> Result<(), FailReason> {
+        if let Some(envelope) = &self.lateral_envelope {
+            // An envelope that can't be evaluated is never passed.
+            let Ok(lateral) = envelope.lever_axis.project(lateral, self.mac.as_ref()) else {
+                return Err(FailReason::LateralOutOfBounds);
+            };
+            let point = match envelope.axis {
+                LateralAxis::Weight => lateral,
+                LateralAxis::Longitudinal => WeightLever::new(longitudinal.lever, lateral.lever),
+            };
+            let vertices = envelope
+                .vertices
+                .map(|vertex| WeightLever::new(vertex[0], vertex[1]));
+            if is_inside_polygon(point, &vertices, false).is_err() {
+                return Err(FailReason::LateralOutOfBounds);
+            }
+        }
+        Ok(())
+    }
+
+    /// Margin from a W&B point to the envelope. The distance is in the units
+    /// of the envelope, while the lever margin is converted back to lever and
+    /// the weight margin is taken at the same lever.
+    pub fn envelope_margin(&self, point: WeightLever) -> Result<EnvelopeMargin, String> {
+        let projected = self.envelope_axis.project(point, self.mac.as_ref())?;
+        let vertices = self.flatten_vertices();
+        let mut margin = envelope_margin(projected, &vertices);
+        let scale = self
+            .envelope_axis
+            .lever_scale(point.weight, self.mac.as_ref())?;
+        margin.lever = margin.lever.map(|lever| lever / scale);
+        if let EnvelopeAxis::Moment(_) = self.envelope_axis {
+            // At the same lever the moment grows with the weight.
+            let sign = if margin.distance < 0.0 { -1.0 } else { 1.0 };
+            margin.weight =
+                weight_margin_along(projected, &vertices, projected.lever / point.weight)
+                    .map(|weight| sign * weight);
+        }
+        Ok(margin)
+    }
+
+    fn is_inside_envelope(&self, point: WeightLever) -> Result<(), FailReason> {
+        // An envelope that can't be evaluated is never passed.
+        let Ok(projected) = self.envelope_axis.project(point, self.mac.as_ref()) else {
+            return Err(FailReason::TorqueOutOfBounds);
+        };
+        is_inside_polygon(projected, &self.flatten_vertices(), false)
+    }
+
     fn is_zero_fuel_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
         let (total_weight, total_torque) = prop
             .0
             .iter()
-            .filter(|(kind, _)| **kind != Kind::Fuel || **kind != Kind::TripFuel)
-            .fold((0.0_f32, 0.0_f32), |acc, (_, wb)| {
+            .filter(|(kind, _)| **kind != Kind::Fuel && **kind != Kind::TripFuel)
+            .fold((0.0_f64, 0.0_f64), |acc, (_, wb)| {
                 (acc.0 + wb.weight, acc.1 + wb.torque())
             });
         let zero_fuel_point = WeightLever::new(total_weight, total_torque / total_weight);
-        if is_inside_polygon(zero_fuel_point, &self.flatten_vertices(), false).is_err() {
+        if self.is_inside_envelope(zero_fuel_point).is_err() {
             return Err(FailReason::ZeroFuel);
         }
         Ok(())
@@ -148,8 +568,7 @@ impl PlaneData {
             if prop
                 .0
                 .get(&Kind::BagageWings)
-                .map(|wings| wings.weight > max_weight_wings)
-                .expect("Config contains bagage in wings but missing in input.")
+                .is_some_and(|wings| wings.weight > max_weight_wings)
             {
                 return Err(FailReason::BagageWings);
             }
@@ -159,22 +578,93 @@ impl PlaneData {
 
     fn is_max_wing_load_ok(&self, properties: &PlaneProperties) -> Result<(), FailReason> {
         if let Some(max_weight) = self.max_weights.max_zero_fuel_mass {
-            let properties_of_interest = [
-                Kind::Base,
-                Kind::Pilot,
-                Kind::CoPilot,
-                Kind::BagageBack,
-                Kind::BagageFront,
-            ];
-            if properties
+            let limit = WeightLimit {
+                name: "wing load".to_string(),
+                stations: self
+                    .wing_load_stations
+                    .clone()
+                    .unwrap_or(DEFAULT_WING_LOAD_STATIONS.to_vec()),
+                max_weight,
+            };
+            if limit.is_exceeded(properties) {
+                return Err(FailReason::MaxWingLoad);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_floor_load_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        for area in &self.cargo_areas {
+            if let Some(item) = prop.0.get(&area.station) {
+                // Items without a footprint are assumed to be spread over the whole floor.
+                let contact_area = prop
+                    .1
+                    .get(&area.station)
+                    .map(|footprint| footprint.min(area.floor_area))
+                    .unwrap_or(area.floor_area);
+                if item.weight / contact_area > area.max_floor_load {
+                    return Err(FailReason::FloorLoad(area.station));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_min_weights_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        for (kind, min_weight) in &self.min_weights {
+            if prop.0.get(kind).map(|wb| wb.weight).unwrap_or(0.0) < *min_weight {
+                return Err(FailReason::MinWeight(*kind));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with the names of all the combined limits that are exceeded.
+    fn is_combined_limits_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        let exceeded = self
+            .limits
+            .iter()
+            .filter(|limit| limit.is_exceeded(prop))
+            .map(|limit| limit.name.clone())
+            .collect::<Vec<String>>();
+        if !exceeded.is_empty() {
+            return Err(FailReason::CombinedLimit(exceeded));
+        }
+        Ok(())
+    }
+
+    fn is_cockpit_load_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        if let Some(glider) = &self.glider {
+            let cockpit_load = prop
                 .0
-                .iter()
-                .filter(|(k, _)| properties_of_interest.contains(k))
-                .map(|(_, wb)| wb.weight)
-                .sum::<f32>()
-                > max_weight
+                .get(&Kind::Pilot)
+                .map(|pilot| pilot.weight)
+                .unwrap_or(0.0);
+            if cockpit_load < glider.min_cockpit_load {
+                return Err(FailReason::MinCockpitLoad);
+            }
+            if cockpit_load > glider.max_cockpit_load {
+                return Err(FailReason::MaxCockpitLoad);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_ballast_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
+        if let Some(glider) = &self.glider {
+            if !(glider
+                .max_water_ballast
+                .map(|weight| is_value_within_weight_limit(&prop.0, Kind::WaterBallast, weight))
+                .unwrap_or(true))
             {
-                return Err(FailReason::MaxWingLoad);
+                return Err(FailReason::WaterBallast);
+            }
+            if !(glider
+                .max_tail_ballast
+                .map(|weight| is_value_within_weight_limit(&prop.0, Kind::TailBallast, weight))
+                .unwrap_or(true))
+            {
+                return Err(FailReason::TailBallast);
             }
         }
         Ok(())
@@ -209,6 +699,11 @@ impl PlaneData {
         self.is_max_wing_load_ok(prop)?;
         self.is_bagage_in_wings_ok(prop)?;
         self.is_bagage_ok(prop)?;
+        self.is_floor_load_ok(prop)?;
+        self.is_min_weights_ok(prop)?;
+        self.is_combined_limits_ok(prop)?;
+        self.is_cockpit_load_ok(prop)?;
+        self.is_ballast_ok(prop)?;
         self.is_fuel_weight_ok(prop)?;
         self.is_zero_fuel_ok(prop)?;
         self.is_landing_fuel_ok(prop)?;
@@ -218,14 +713,28 @@ impl PlaneData {
     }
 }
 
-#[derive(Default)]
-pub struct PlaneProperties(HashMap<Kind, WeightLever>);
+/// Loaded stations together with the footprint (m²) of the items at each station.
+#[derive(Default, Clone)]
+pub struct PlaneProperties(HashMap<Kind, WeightLever>, HashMap<Kind, f64>);
 
 impl PlaneProperties {
     pub fn new(val: HashMap<Kind, WeightLever>) -> PlaneProperties {
-        PlaneProperties(val)
+        PlaneProperties(val, HashMap::new())
+    }
+
+    pub fn get(&self, kind: &Kind) -> Option<&WeightLever> {
+        self.0.get(kind)
     }
-    fn get_total_weights(&self) -> f32 {
+
+    pub fn insert(&mut self, kind: Kind, value: WeightLever) {
+        self.0.insert(kind, value);
+    }
+
+    pub fn with_footprints(mut self, footprints: HashMap<Kind, f64>) -> PlaneProperties {
+        self.1 = footprints;
+        self
+    }
+    pub(crate) fn get_total_weights(&self) -> f64 {
         self.0
             .iter()
             .filter(|(k, _)| **k != Kind::TripFuel)
@@ -233,16 +742,16 @@ impl PlaneProperties {
             .sum()
     }
 
-    fn get_landing_weights(&self) -> f32 {
-        self.get_total_weights()
-            - self
-                .0
-                .get(&Kind::TripFuel)
-                .expect("Missing Trip fuel")
-                .weight
+    /// Trip fuel, none for planes without fuel like gliders.
+    fn trip_fuel(&self) -> WeightLever {
+        self.0.get(&Kind::TripFuel).copied().unwrap_or_default()
     }
 
-    fn get_total_torque(&self) -> f32 {
+    fn get_landing_weights(&self) -> f64 {
+        self.get_total_weights() - self.trip_fuel().weight
+    }
+
+    fn get_total_torque(&self) -> f64 {
         self.0
             .iter()
             .filter(|(k, _)| **k != Kind::TripFuel)
@@ -250,20 +759,14 @@ impl PlaneProperties {
             .sum()
     }
 
-    fn get_landing_torque(&self) -> f32 {
-        self.get_total_torque()
-            - self
-                .0
-                .get(&Kind::TripFuel)
-                .expect("Missing Trip fuel")
-                .torque()
+    fn get_landing_torque(&self) -> f64 {
+        self.get_total_torque() - self.trip_fuel().torque()
     }
 }
 
 impl WeightAndBalance for PlaneData {
     fn calc_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever {
         let total_weight = prop.get_total_weights();
-        assert!(total_weight > 0.0);
         WeightLever {
             weight: total_weight,
             lever: prop.get_total_torque() / total_weight,
@@ -272,26 +775,44 @@ impl WeightAndBalance for PlaneData {
 
     fn calc_landing_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever {
         let total_weight = prop.get_landing_weights();
-        assert!(total_weight > 0.0);
         WeightLever {
             weight: total_weight,
             lever: prop.get_landing_torque() / total_weight,
         }
     }
 
+    fn calc_lateral_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever {
+        let total_weight = prop.get_total_weights();
+        WeightLever {
+            weight: total_weight,
+            lever: self.get_lateral_torque(prop) / total_weight,
+        }
+    }
+
+    fn calc_landing_lateral_weight_and_balance(&self, prop: &PlaneProperties) -> WeightLever {
+        let total_weight = prop.get_landing_weights();
+        WeightLever {
+            weight: total_weight,
+            lever: self.get_landing_lateral_torque(prop) / total_weight,
+        }
+    }
+
     fn is_weight_and_balance_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
         self.check_limits(prop)?;
         let calc = self.calc_weight_and_balance(prop);
-        is_inside_polygon(calc, &self.flatten_vertices(), false)?;
+        self.is_inside_envelope(calc)?;
+        self.is_lateral_ok(calc, self.calc_lateral_weight_and_balance(prop))?;
 
         Ok(())
     }
 
     fn is_landing_weight_and_balance_ok(&self, prop: &PlaneProperties) -> Result<(), FailReason> {
         self.check_limits(prop)?;
+        self.is_mlw_ok(prop)?;
 
         let calc = self.calc_landing_weight_and_balance(prop);
-        is_inside_polygon(calc, &self.flatten_vertices(), false)?;
+        self.is_inside_envelope(calc)?;
+        self.is_lateral_ok(calc, self.calc_landing_lateral_weight_and_balance(prop))?;
         Ok(())
     }
 }
@@ -300,6 +821,21 @@ impl WeightAndBalance for PlaneData {
 mod tests {
     use super::*;
 
+    #[test]
+    fn large_moments_keep_precision() {
+        let data = PlaneData::default();
+        let prop = PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(612.3, 241.3)),
+            (Kind::Fuel, WeightLever::new(129.1, 241.3)),
+            (Kind::Pilot, WeightLever::new(93.7, 241.3)),
+            (Kind::CoPilot, WeightLever::new(77.9, 241.3)),
+        ]));
+
+        let point = data.calc_weight_and_balance(&prop);
+        assert!((point.weight - 913.0).abs() < 1e-9);
+        assert!((point.lever - 241.3).abs() < 1e-9);
+    }
+
     #[test]
     fn mtow_good() {
         let mut data = PlaneData::default();
@@ -359,6 +895,419 @@ mod tests {
         );
     }
 
+    #[test]
+    fn max_wing_load_configured_stations() {
+        let mut data = PlaneData::default();
+        data.max_weights.max_zero_fuel_mass = Some(10.0);
+        data.wing_load_stations = Some(vec![Kind::Base, Kind::PaxLeftBack]);
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(6.0, 1.0));
+        prop.0.insert(Kind::Pilot, WeightLever::new(6.0, 1.0));
+
+        assert!(data.is_max_wing_load_ok(&prop).is_ok());
+        prop.0.insert(Kind::PaxLeftBack, WeightLever::new(6.0, 1.0));
+        assert_eq!(
+            data.is_max_wing_load_ok(&prop).unwrap_err(),
+            FailReason::MaxWingLoad
+        );
+    }
+
+    fn moment_plane() -> PlaneData {
+        PlaneData {
+            envelope_axis: EnvelopeAxis::Moment(1000.0),
+            vertices: [
+                [500.0, 85.0],
+                [600.0, 102.0],
+                [750.0, 131.0],
+                [750.0, 138.0],
+                [600.0, 110.0],
+                [500.0, 92.0],
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn moment_envelope_good() {
+        let data = moment_plane();
+
+        assert!(data
+            .is_inside_envelope(WeightLever::new(600.0, 176.0))
+            .is_ok());
+    }
+
+    #[test]
+    fn moment_envelope_bad() {
+        let data = moment_plane();
+
+        assert_eq!(
+            data.is_inside_envelope(WeightLever::new(600.0, 190.0))
+                .unwrap_err(),
+            FailReason::TorqueOutOfBounds
+        );
+    }
+
+    #[test]
+    fn moment_envelope_margin() {
+        let data = moment_plane();
+        let margin = data
+            .envelope_margin(WeightLever::new(600.0, 175.0))
+            .unwrap();
+
+        assert_eq!(margin.edge, 1);
+        assert!((margin.lever.unwrap() - 5.0).abs() < 0.01);
+        // Down to the minimum weight of 500 kg along the 175 lever.
+        assert!((margin.weight.unwrap() - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn percent_mac_envelope_without_mac() {
+        let mut data = valid_plane();
+        data.envelope_axis = EnvelopeAxis::PercentMac;
+        let point = WeightLever::new(600.0, 180.0);
+
+        assert_eq!(
+            data.is_inside_envelope(point),
+            Err(FailReason::TorqueOutOfBounds)
+        );
+        assert_eq!(
+            data.envelope_margin(point).unwrap_err(),
+            "Envelope in percent of MAC but MAC missing"
+        );
+    }
+
+    #[test]
+    fn percent_mac() {
+        let mac = Mac {
+            leading_edge: 150.0,
+            length: 40.0,
+        };
+
+        assert_eq!(mac.percent(160.0), 25.0);
+        assert_eq!(mac.lever(25.0), 160.0);
+    }
+
+    #[test]
+    fn percent_mac_envelope() {
+        let data = PlaneData {
+            mac: Some(Mac {
+                leading_edge: 150.0,
+                length: 40.0,
+            }),
+            envelope_axis: EnvelopeAxis::PercentMac,
+            vertices: [
+                [500.0, 15.0],
+                [600.0, 15.0],
+                [750.0, 20.0],
+                [750.0, 35.0],
+                [600.0, 35.0],
+                [500.0, 35.0],
+            ],
+            ..Default::default()
+        };
+
+        assert!(data
+            .is_inside_envelope(WeightLever::new(600.0, 160.0))
+            .is_ok());
+        assert_eq!(
+            data.is_inside_envelope(WeightLever::new(600.0, 170.0))
+                .unwrap_err(),
+            FailReason::TorqueOutOfBounds
+        );
+    }
+
+    fn lateral_plane() -> PlaneData {
+        let mut data = PlaneData::default();
+        data.lateral_levers.insert(Kind::BagageWings, 100.0);
+        data.lateral_envelope = Some(LateralEnvelope {
+            axis: LateralAxis::Weight,
+            lever_axis: EnvelopeAxis::Lever,
+            vertices: [
+                [0.0, -5.0],
+                [50.0, -5.0],
+                [100.0, -5.0],
+                [100.0, 5.0],
+                [50.0, 5.0],
+                [0.0, 5.0],
+            ],
+        });
+        data
+    }
+
+    #[test]
+    fn lateral_cg() {
+        let data = lateral_plane();
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(90.0, 1.0));
+        prop.0
+            .insert(Kind::BagageWings, WeightLever::new(10.0, 1.0));
+
+        assert_eq!(
+            data.calc_lateral_weight_and_balance(&prop),
+            WeightLever::new(100.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn lateral_good() {
+        let data = lateral_plane();
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(95.0, 1.0));
+        prop.0.insert(Kind::BagageWings, WeightLever::new(2.0, 1.0));
+        let lateral = data.calc_lateral_weight_and_balance(&prop);
+
+        assert!(data.is_lateral_ok(WeightLever::default(), lateral).is_ok());
+    }
+
+    #[test]
+    fn lateral_bad() {
+        let data = lateral_plane();
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(90.0, 1.0));
+        prop.0
+            .insert(Kind::BagageWings, WeightLever::new(10.0, 1.0));
+        let lateral = data.calc_lateral_weight_and_balance(&prop);
+
+        assert_eq!(
+            data.is_lateral_ok(WeightLever::default(), lateral)
+                .unwrap_err(),
+            FailReason::LateralOutOfBounds
+        );
+    }
+
+    #[test]
+    fn lateral_percent_mac_rejected() {
+        let mut data = valid_plane();
+        data.lateral_envelope = lateral_plane().lateral_envelope;
+        data.mac = Some(Mac {
+            leading_edge: 0.0,
+            length: 100.0,
+        });
+        data.lateral_envelope.as_mut().unwrap().lever_axis = EnvelopeAxis::PercentMac;
+        let json = serde_json::to_string(&data).unwrap();
+
+        assert!(serde_json::from_str::<PlaneData>(&json)
+            .unwrap_err()
+            .to_string()
+            .contains("Lateral envelope cannot be in percent of MAC"));
+        assert_eq!(
+            data.validate(),
+            Err("Lateral envelope cannot be in percent of MAC".to_string())
+        );
+    }
+
+    #[test]
+    fn floor_load_spread_good() {
+        let mut data = PlaneData::default();
+        data.cargo_areas.push(CargoArea {
+            station: Kind::Bagage,
+            floor_area: 0.5,
+            max_floor_load: 100.0,
+        });
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Bagage, WeightLever::new(40.0, 1.0));
+
+        assert!(data.is_floor_load_ok(&prop).is_ok());
+    }
+
+    #[test]
+    fn floor_load_point_load_bad() {
+        let mut data = PlaneData::default();
+        data.cargo_areas.push(CargoArea {
+            station: Kind::Bagage,
+            floor_area: 0.5,
+            max_floor_load: 100.0,
+        });
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Bagage, WeightLever::new(40.0, 1.0));
+        prop.1.insert(Kind::Bagage, 0.2);
+
+        assert_eq!(
+            data.is_floor_load_ok(&prop).unwrap_err(),
+            FailReason::FloorLoad(Kind::Bagage)
+        );
+    }
+
+    #[test]
+    fn min_weight_good() {
+        let mut data = PlaneData::default();
+        data.min_weights.insert(Kind::Pilot, 55.0);
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Pilot, WeightLever::new(60.0, 1.0));
+
+        assert!(data.is_min_weights_ok(&prop).is_ok());
+    }
+
+    #[test]
+    fn min_weight_bad() {
+        let mut data = PlaneData::default();
+        data.min_weights.insert(Kind::Pilot, 55.0);
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Pilot, WeightLever::new(50.0, 1.0));
+
+        assert_eq!(
+            data.is_min_weights_ok(&prop).unwrap_err(),
+            FailReason::MinWeight(Kind::Pilot)
+        );
+    }
+
+    #[test]
+    fn min_weight_first_station_reported() {
+        let mut data = PlaneData::default();
+        data.min_weights.insert(Kind::PaxRightBack, 10.0);
+        data.min_weights.insert(Kind::Pilot, 55.0);
+        data.min_weights.insert(Kind::Bagage, 10.0);
+        let prop = PlaneProperties::default();
+
+        for _ in 0..10 {
+            assert_eq!(
+                data.is_min_weights_ok(&prop).unwrap_err(),
+                FailReason::MinWeight(Kind::Bagage)
+            );
+        }
+    }
+
+    #[test]
+    fn combined_limit_good() {
+        let mut data = PlaneData::default();
+        data.limits.push(WeightLimit {
+            name: "bagage areas".to_string(),
+            stations: vec![Kind::BagageFront, Kind::BagageBack],
+            max_weight: 10.0,
+        });
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::BagageFront, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::BagageBack, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::Pilot, WeightLever::new(80.0, 1.0));
+
+        assert!(data.is_combined_limits_ok(&prop).is_ok());
+    }
+
+    #[test]
+    fn combined_limit_bad() {
+        let mut data = PlaneData::default();
+        data.limits.push(WeightLimit {
+            name: "bagage areas".to_string(),
+            stations: vec![Kind::BagageFront, Kind::BagageBack],
+            max_weight: 10.0,
+        });
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::BagageFront, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::BagageBack, WeightLever::new(6.0, 1.0));
+
+        assert_eq!(
+            data.is_combined_limits_ok(&prop).unwrap_err(),
+            FailReason::CombinedLimit(vec!["bagage areas".to_string()])
+        );
+    }
+
+    #[test]
+    fn combined_limits_all_reported() {
+        let mut data = PlaneData::default();
+        data.limits.push(WeightLimit {
+            name: "bagage areas".to_string(),
+            stations: vec![Kind::BagageFront, Kind::BagageBack],
+            max_weight: 10.0,
+        });
+        data.limits.push(WeightLimit {
+            name: "front seats".to_string(),
+            stations: vec![Kind::Pilot, Kind::CoPilot],
+            max_weight: 150.0,
+        });
+        data.limits.push(WeightLimit {
+            name: "bagage front".to_string(),
+            stations: vec![Kind::BagageFront],
+            max_weight: 10.0,
+        });
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::BagageFront, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::BagageBack, WeightLever::new(6.0, 1.0));
+        prop.0.insert(Kind::Pilot, WeightLever::new(80.0, 1.0));
+        prop.0.insert(Kind::CoPilot, WeightLever::new(80.0, 1.0));
+
+        assert_eq!(
+            data.is_combined_limits_ok(&prop).unwrap_err(),
+            FailReason::CombinedLimit(vec!["bagage areas".to_string(), "front seats".to_string()])
+        );
+    }
+
+    #[test]
+    fn mlw_good() {
+        let mut data = PlaneData::default();
+        data.max_weights.max_landing_weight = Some(10.0);
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(9.0, 1.0));
+        prop.0.insert(Kind::Fuel, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::TripFuel, WeightLever::new(4.0, 1.0));
+
+        assert!(data.is_mlw_ok(&prop).is_ok());
+    }
+
+    #[test]
+    fn mlw_bad() {
+        let mut data = PlaneData::default();
+        data.max_weights.max_landing_weight = Some(10.0);
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(9.0, 1.0));
+        prop.0.insert(Kind::Fuel, WeightLever::new(5.0, 1.0));
+        prop.0.insert(Kind::TripFuel, WeightLever::new(3.0, 1.0));
+
+        assert_eq!(
+            data.is_mlw_ok(&prop).unwrap_err(),
+            FailReason::MaxLandingWeight
+        );
+    }
+
+    #[test]
+    fn zero_fuel_excludes_fuel() {
+        let data = PlaneData {
+            vertices: [
+                [0.0, 10.0],
+                [50.0, 10.0],
+                [100.0, 10.0],
+                [100.0, 20.0],
+                [50.0, 20.0],
+                [0.0, 20.0],
+            ],
+            ..Default::default()
+        };
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(90.0, 15.0));
+        prop.0.insert(Kind::Fuel, WeightLever::new(30.0, 15.0));
+        prop.0.insert(Kind::TripFuel, WeightLever::new(20.0, 15.0));
+
+        assert!(data.is_zero_fuel_ok(&prop).is_ok());
+    }
+
+    #[test]
+    fn zero_fuel_aft_fuel_not_counted() {
+        let mut data = PlaneData {
+            vertices: [
+                [0.0, 10.0],
+                [500.0, 10.0],
+                [1000.0, 10.0],
+                [1000.0, 20.0],
+                [500.0, 20.0],
+                [0.0, 20.0],
+            ],
+            ..Default::default()
+        };
+        let mut prop = PlaneProperties::default();
+        prop.0.insert(Kind::Base, WeightLever::new(100.0, 15.0));
+        // Fuel far aft would move the CG out of the envelope if it was counted.
+        prop.0.insert(Kind::Fuel, WeightLever::new(100.0, 40.0));
+        prop.0.insert(Kind::TripFuel, WeightLever::new(50.0, 40.0));
+
+        assert!(data.is_zero_fuel_ok(&prop).is_ok());
+        data.vertices[0][1] = 16.0;
+        data.vertices[1][1] = 16.0;
+        data.vertices[2][1] = 16.0;
+        assert_eq!(
+            data.is_zero_fuel_ok(&prop).unwrap_err(),
+            FailReason::ZeroFuel
+        );
+    }
+
     #[test]
     fn landing_weight_zero() {
         let mut prop = PlaneProperties::default();
@@ -383,4 +1332,206 @@ mod tests {
 
         assert_eq!(prop.get_landing_weights(), -5.0);
     }
+
+    #[test]
+    fn content_hash_changes_with_data() {
+        let mut data = PlaneData::default();
+        data.default_loading.insert(Kind::Base, 450.0);
+        data.default_loading.insert(Kind::Pilot, 80.0);
+        let hash = data.content_hash();
+        let mut reordered = PlaneData::default();
+        reordered.default_loading.insert(Kind::Pilot, 80.0);
+        reordered.default_loading.insert(Kind::Base, 450.0);
+
+        assert_eq!(reordered.content_hash(), hash);
+        data.revision = Some("4".to_string());
+        data.effective_date = Some("2024-06-01".to_string());
+        assert_eq!(data.content_hash(), hash);
+        data.lateral_levers.insert(Kind::Pilot, -0.3);
+        let lateral = data.content_hash();
+        assert_ne!(lateral, hash);
+        data.levers.pilot = 1.0;
+        assert_ne!(data.content_hash(), lateral);
+    }
+
+    #[test]
+    fn properties_of_every_station_with_weight() {
+        let plane = valid_plane();
+        let mut values = HashMap::from([(Kind::Base, 450.0)]);
+        for kind in plane.to_lever_map().into_keys() {
+            values.entry(kind).or_insert(0.0);
+        }
+        let prop = plane.properties(&values, HashMap::new()).unwrap();
+
+        assert_eq!(prop.get_total_weights(), 450.0);
+        values.insert(Kind::Base, 0.0);
+        assert_eq!(
+            plane.properties(&values, HashMap::new()).err(),
+            Some("Total weight must be positive at take-off and landing".to_string())
+        );
+        values.remove(&Kind::Pilot);
+        assert_eq!(
+            plane.properties(&values, HashMap::new()).err(),
+            Some("Missing weight for station: pilot".to_string())
+        );
+    }
+
+    #[test]
+    fn bagage_in_wings_limit_without_station() {
+        let mut plane = valid_plane();
+        plane.max_weights.max_bagage_weight_wings = Some(10.0);
+        let prop = PlaneProperties::new(HashMap::from([(
+            Kind::Base,
+            WeightLever::new(600.0, 180.0),
+        )]));
+
+        assert_eq!(plane.is_bagage_in_wings_ok(&prop), Ok(()));
+    }
+
+    fn valid_plane() -> PlaneData {
+        let mut data = PlaneData {
+            name: "SEMOA".to_string(),
+            vertices: [
+                [500.0, 170.0],
+                [750.0, 175.0],
+                [750.0, 190.0],
+                [500.0, 190.0],
+                [500.0, 180.0],
+                [500.0, 175.0],
+            ],
+            ..Default::default()
+        };
+        data.levers.fuel = Some(180.0);
+        data.levers.trip_fuel = Some(180.0);
+        data.max_weights.max_fuel_weight = 85.0;
+        data.max_weights.max_take_off_weight = 750.0;
+        data
+    }
+
+    #[test]
+    fn validate_good() {
+        assert!(valid_plane().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_bad() {
+        let mut no_envelope = valid_plane();
+        no_envelope.vertices = [[0.0; 2]; 6];
+        let mut percent_mac = valid_plane();
+        percent_mac.envelope_axis = EnvelopeAxis::PercentMac;
+        let mut default_without_lever = valid_plane();
+        default_without_lever
+            .default_loading
+            .insert(Kind::Bagage, 10.0);
+
+        assert!(no_envelope.validate().is_err());
+        assert!(percent_mac.validate().is_err());
+        assert_eq!(
+            default_without_lever.validate(),
+            Err("Station Bagage has no lever".to_string())
+        );
+        assert_eq!(
+            validate_planes(&[valid_plane(), valid_plane()]),
+            Err("Plane SEMOA is in the config twice".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_envelopes() {
+        let lateral = |lever_axis, vertices| {
+            let mut data = valid_plane();
+            data.lateral_envelope = Some(LateralEnvelope {
+                lever_axis,
+                vertices,
+                ..Default::default()
+            });
+            data.validate()
+        };
+        let square = [
+            [500.0, -1.0],
+            [750.0, -1.0],
+            [750.0, 1.0],
+            [500.0, 1.0],
+            [500.0, 0.0],
+            [500.0, -1.0],
+        ];
+        let mut nan = square;
+        nan[2][1] = f64::NAN;
+        let mut moment = valid_plane();
+        moment.envelope_axis = EnvelopeAxis::Moment(f64::INFINITY);
+        let mut mac = valid_plane();
+        mac.mac = Some(Mac {
+            leading_edge: 160.0,
+            length: f64::NAN,
+        });
+
+        assert!(lateral(EnvelopeAxis::Lever, square).is_ok());
+        assert_eq!(
+            lateral(EnvelopeAxis::Moment(0.0), square),
+            Err("Lateral envelope moment divisor must be a number other than 0".to_string())
+        );
+        assert_eq!(
+            lateral(EnvelopeAxis::Lever, nan),
+            Err("Lateral envelope vertices must be numbers".to_string())
+        );
+        assert_eq!(
+            lateral(EnvelopeAxis::Lever, [[0.0; 2]; 6]),
+            Err("Lateral envelope has no area".to_string())
+        );
+        assert_eq!(
+            moment.validate(),
+            Err("Envelope moment divisor must be a number other than 0".to_string())
+        );
+        assert_eq!(
+            mac.validate(),
+            Err("MAC length must be positive".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_glider() {
+        let mut data = valid_plane();
+        data.max_weights.max_fuel_weight = 0.0;
+        assert_eq!(
+            data.validate(),
+            Err("max_fuel_weight must be positive".to_string())
+        );
+        data.levers.fuel = None;
+        data.levers.trip_fuel = None;
+        assert_eq!(
+            data.validate(),
+            Err("Levers for fuel and trip_fuel are required".to_string())
+        );
+
+        data.glider = Some(GliderData {
+            wing_area: 10.5,
+            min_cockpit_load: 70.0,
+            max_cockpit_load: 110.0,
+            ..Default::default()
+        });
+        assert!(data.validate().is_ok());
+        data.glider.as_mut().unwrap().wing_area = 0.0;
+        assert_eq!(
+            data.validate(),
+            Err("Glider wing_area must be positive".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_floor_area() {
+        let mut data = valid_plane();
+        data.levers.bagage = Some(200.0);
+        data.cargo_areas.push(CargoArea {
+            station: Kind::Bagage,
+            floor_area: 0.0,
+            max_floor_load: 100.0,
+        });
+
+        assert_eq!(
+            data.validate(),
+            Err("Cargo floor_area must be positive".to_string())
+        );
+        data.cargo_areas[0].floor_area = 0.5;
+        assert!(data.validate().is_ok());
+    }
 }
@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{planes::Input, seating::SEATS, Kind};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    /// Body weight in kg.
+    pub weight: f64,
+    /// Typical baggage in kg.
+    #[serde(default)]
+    pub bagage: f64,
+}
+
+/// Saved pilots and passengers. Loadings refer to them by name, e.g. `"@anna"`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Profiles {
+    /// Added to the body weight of everyone in a seat, see `SEATS`.
+    #[serde(default)]
+    pub clothing_allowance: f64,
+    #[serde(default)]
+    pub people: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Adds or updates the profile. Names are used in references, so they
+    /// can't contain `+` or whitespace or start with `@`, and weights must not
+    /// be negative.
+    pub fn set(&mut self, name: &str, profile: Profile) -> Result<(), String> {
+        if name.is_empty()
+            || name.starts_with('@')
+            || name.contains(|c: char| c == '+' || c.is_whitespace())
+        {
+            return Err(format!("Invalid profile name: {:?}", name));
+        }
+        for weight in [profile.weight, profile.bagage] {
+            if weight < 0.0 || !weight.is_finite() {
+                return Err(format!("Weight must not be negative: {}", weight));
+            }
+        }
+        self.people.insert(name.to_string(), profile);
+        Ok(())
+    }
+
+    /// Weight of a station value like `"@anna"` or `"@anna+@bertil"`. On
+    /// baggage stations the typical baggage of the people is used, otherwise
+    /// their body weight, with the clothing allowance in seats.
+    pub fn resolve(&self, kind: Kind, value: &str) -> Result<f64, String> {
+        value
+            .split('+')
+            .map(|reference| {
+                let name = reference
+                    .trim()
+                    .strip_prefix('@')
+                    .ok_or_else(|| format!("Expected a profile like @name, got: {}", reference))?;
+                let profile = self
+                    .people
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown profile: {}", name))?;
+                Ok(match kind {
+                    Kind::Bagage | Kind::BagageFront | Kind::BagageBack | Kind::BagageWings => {
+                        profile.bagage
+                    }
+                    kind if SEATS.contains(&kind) => profile.weight + self.clothing_allowance,
+                    _ => profile.weight,
+                })
+            })
+            .sum()
+    }
+}
+
+/// Reads the profiles, or no profiles if the file does not exist.
+pub fn read_profiles(path: &str) -> Profiles {
+    match File::open(path) {
+        Ok(file) => {
+            serde_json::from_reader(BufReader::new(file)).expect("Invalid format of profiles")
+        }
+        Err(_) => Profiles::default(),
+    }
+}
+
+pub fn write_profiles(path: &str, profiles: &Profiles) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, profiles)?;
+    Ok(())
+}
+
+/// Replaces profile references, values starting with `@`, among the input
+/// values with weights.
+pub fn resolve_profiles(input: &mut Input, profiles: &Profiles) -> Result<(), String> {
+    for (key, value) in input.values.iter_mut() {
+        if let Some(reference) = value.as_str().filter(|value| value.starts_with('@')) {
+            let kind = Kind::from_str(key)?;
+            *value = serde_json::Value::from(profiles.resolve(kind, reference)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> Profiles {
+        Profiles {
+            clothing_allowance: 2.0,
+            people: BTreeMap::from([
+                (
+                    "anna".to_string(),
+                    Profile {
+                        weight: 60.0,
+                        bagage: 5.0,
+                    },
+                ),
+                (
+                    "bertil".to_string(),
+                    Profile {
+                        weight: 80.0,
+                        bagage: 10.0,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn resolve_seat() {
+        assert_eq!(profiles().resolve(Kind::Pilot, "@anna"), Ok(62.0));
+    }
+
+    #[test]
+    fn resolve_bagage_of_several() {
+        assert_eq!(
+            profiles().resolve(Kind::BagageBack, "@anna + @bertil"),
+            Ok(15.0)
+        );
+    }
+
+    #[test]
+    fn clothing_only_in_seats() {
+        assert_eq!(profiles().resolve(Kind::PaxRightBack, "@anna"), Ok(62.0));
+        assert_eq!(profiles().resolve(Kind::WaterBallast, "@anna"), Ok(60.0));
+    }
+
+    #[test]
+    fn set_rejects_invalid() {
+        let mut profiles = profiles();
+        let profile = |weight, bagage| Profile { weight, bagage };
+
+        assert!(profiles.set("cecilia", profile(70.0, 0.0)).is_ok());
+        assert_eq!(profiles.people["cecilia"].weight, 70.0);
+        for name in ["", "anna+bertil", "anna b", "@anna"] {
+            assert_eq!(
+                profiles.set(name, profile(70.0, 0.0)),
+                Err(format!("Invalid profile name: {:?}", name))
+            );
+        }
+        assert_eq!(
+            profiles.set("anna", profile(-60.0, 0.0)),
+            Err("Weight must not be negative: -60".to_string())
+        );
+        assert!(profiles.set("anna", profile(60.0, -5.0)).is_err());
+        assert_eq!(profiles.people["anna"].weight, 60.0);
+    }
+
+    #[test]
+    fn resolve_unknown() {
+        assert!(profiles().resolve(Kind::Pilot, "@cecilia").is_err());
+    }
+
+    #[test]
+    fn resolve_input() {
+        let mut input = Input::default();
+        input
+            .values
+            .insert("co_pilot".to_string(), serde_json::Value::from("@bertil"));
+        input
+            .values
+            .insert("base".to_string(), serde_json::Value::from(450.0));
+        resolve_profiles(&mut input, &profiles()).unwrap();
+
+        assert_eq!(input.values["co_pilot"].as_f64(), Some(82.0));
+        assert_eq!(input.values["base"].as_f64(), Some(450.0));
+    }
+}
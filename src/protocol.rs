@@ -0,0 +1,276 @@
+use std::{error::Error, fmt, io};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{history::Outcome, planes::Input, seating::SeatAssignment};
+
+/// Version of the messages below. A server answers requests of another
+/// version with an error.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Longest message in bytes, including the newline.
+pub const MAX_MESSAGE_LENGTH: u64 = 1024 * 1024;
+
+/// The wire protocol is newline-delimited JSON over TCP: every request and
+/// response is one JSON object on a line of its own, e.g.
+/// `{"version":1,"type":"calculate","input":{...}}`. A connection can carry
+/// any number of requests and every request gets one response, in order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Request {
+    pub version: u32,
+    /// Token the server may require on every request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(flatten)]
+    pub body: RequestBody,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum RequestBody {
+    /// W&B of the loading in the input.
+    #[serde(rename = "calculate")]
+    Calculate { input: Input },
+    /// Seatings of the people in the input that pass W&B, best margin first.
+    #[serde(rename = "assign_seats")]
+    AssignSeats { input: Input },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Response {
+    pub version: u32,
+    #[serde(flatten)]
+    pub body: ResponseBody,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum ResponseBody {
+    #[serde(rename = "outcome")]
+    Outcome { outcome: Outcome },
+    #[serde(rename = "seats")]
+    Seats { seats: Vec<SeatAssignment> },
+    /// The request could not be handled, the connection stays open.
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+impl Request {
+    pub fn new(body: RequestBody) -> Request {
+        Request {
+            version: PROTOCOL_VERSION,
+            token: None,
+            body,
+        }
+    }
+}
+
+impl RequestBody {
+    /// The `type` of the request, e.g. `calculate`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RequestBody::Calculate { .. } => "calculate",
+            RequestBody::AssignSeats { .. } => "assign_seats",
+        }
+    }
+
+    pub fn input(&self) -> &Input {
+        match self {
+            RequestBody::Calculate { input } | RequestBody::AssignSeats { input } => input,
+        }
+    }
+}
+
+impl Response {
+    pub fn new(body: ResponseBody) -> Response {
+        Response {
+            version: PROTOCOL_VERSION,
+            body,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Response {
+        Response::new(ResponseBody::Error {
+            message: message.into(),
+        })
+    }
+}
+
+/// Error of `read_message` when a message is longer than `MAX_MESSAGE_LENGTH`.
+#[derive(Debug)]
+pub struct MessageTooLong;
+
+impl fmt::Display for MessageTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Message longer than {} bytes", MAX_MESSAGE_LENGTH)
+    }
+}
+
+impl Error for MessageTooLong {}
+
+impl MessageTooLong {
+    pub fn is(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<MessageTooLong>())
+    }
+}
+
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+/// Reads the next message. `Ok(None)` when the other side closed the
+/// connection, the inner error when the line is not a valid message, also
+/// when it is not UTF-8. The
+/// version is checked first, so a message of another version gets a version
+/// error even if the rest of it is unknown.
+pub async fn read_message<T: DeserializeOwned>(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> io::Result<Option<Result<T, String>>> {
+    let mut bytes = Vec::new();
+    let n = reader
+        .take(MAX_MESSAGE_LENGTH)
+        .read_until(b'\n', &mut bytes)
+        .await?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if !bytes.ends_with(b"\n") && n as u64 == MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, MessageTooLong));
+    }
+    let invalid = |e: &dyn fmt::Display| format!("Invalid message: {}", e);
+    let line = match std::str::from_utf8(&bytes) {
+        Ok(line) => line,
+        Err(e) => return Ok(Some(Err(invalid(&e)))),
+    };
+    Ok(Some(
+        serde_json::from_str::<Versioned>(line)
+            .map_err(|e| invalid(&e))
+            .and_then(|Versioned { version }| {
+                if version != PROTOCOL_VERSION {
+                    return Err(format!(
+                        "Unsupported protocol version {}, expected {}",
+                        version, PROTOCOL_VERSION
+                    ));
+                }
+                serde_json::from_str(line).map_err(|e| invalid(&e))
+            }),
+    ))
+}
+
+pub async fn write_message<T: Serialize>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &T,
+) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, BufReader};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn message_split_across_writes() {
+        let (mut client, server) = duplex(64);
+        let mut server = BufReader::new(server);
+        let request = r#"{"version":1,"type":"calculate","input":{"name":"SEMOA","values":{}}}"#;
+        let (first, second) = request.split_at(20);
+
+        tokio::spawn(async move {
+            client.write_all(first.as_bytes()).await.unwrap();
+            client.write_all(second.as_bytes()).await.unwrap();
+            client.write_all(b"\n").await.unwrap();
+        });
+        let request = read_message::<Request>(&mut server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(request.version, PROTOCOL_VERSION);
+        assert!(matches!(request.body, RequestBody::Calculate { input } if input.name == "SEMOA"));
+        assert!(read_message::<Request>(&mut server)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn response_round_trip() {
+        let (mut writer, reader) = duplex(1024);
+        let mut reader = BufReader::new(reader);
+        let response = Response::error("Plane missing in config: NOPLANE");
+
+        write_message(&mut writer, &response).await.unwrap();
+        write_message(&mut writer, &response).await.unwrap();
+
+        for _ in 0..2 {
+            assert_eq!(
+                read_message::<Response>(&mut reader).await.unwrap(),
+                Some(Ok(Response::error("Plane missing in config: NOPLANE")))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn version_checked_first() {
+        let mut reader = BufReader::new(&b"{\"version\":0,\"type\":\"fly\"}\n"[..]);
+
+        assert_eq!(
+            read_message::<Request>(&mut reader)
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap_err(),
+            "Unsupported protocol version 0, expected 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn message_too_long() {
+        let line = vec![b' '; MAX_MESSAGE_LENGTH as usize];
+        let mut reader = BufReader::new(&line[..]);
+
+        let e = read_message::<Request>(&mut reader).await.unwrap_err();
+        assert!(MessageTooLong::is(&e), "{}", e);
+    }
+
+    #[tokio::test]
+    async fn invalid_message() {
+        let mut reader = BufReader::new(&b"{\"version\":1,\"type\":\"fly\"}\n"[..]);
+
+        assert!(read_message::<Request>(&mut reader)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8() {
+        let mut reader = BufReader::new(&b"{\"version\":1,\"type\":\"\xff\"}\n\n"[..]);
+
+        let e = read_message::<Request>(&mut reader)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert!(e.starts_with("Invalid message: invalid utf-8"), "{}", e);
+        // The connection goes on with the next line.
+        assert!(read_message::<Request>(&mut reader)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_err());
+        assert!(read_message::<Request>(&mut reader)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
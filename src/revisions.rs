@@ -0,0 +1,118 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::planes::PlaneData;
+
+/// A changed value in a plane's config. `path` is like `levers.pilot` or
+/// `vertices[2][1]`, a missing old or new value means it was added or removed.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, old, new),
+            (None, Some(new)) => write!(f, "{}: added {}", self.path, new),
+            (Some(old), None) => write!(f, "{}: removed {}", self.path, old),
+            (None, None) => write!(f, "{}: unchanged", self.path),
+        }
+    }
+}
+
+/// Levers, limits, vertices and other values that differ between two
+/// revisions of a plane.
+pub fn diff_planes(old: &PlaneData, new: &PlaneData) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_values(
+        String::new(),
+        &serde_json::to_value(old).expect("PlaneData is serializable"),
+        &serde_json::to_value(new).expect("PlaneData is serializable"),
+        &mut changes,
+    );
+    changes
+}
+
+fn diff_values(path: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_options(path, old.get(key), new.get(key), changes);
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, index);
+                diff_options(path, old.get(index), new.get(index), changes);
+            }
+        }
+        _ if old != new => changes.push(Change {
+            path,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_options(path: String, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Some(old), Some(new)) => diff_values(path, old, new, changes),
+        (None, None) => {}
+        _ => changes.push(Change {
+            path,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{planes::WeightLimit, Kind};
+
+    #[test]
+    fn diff_levers_limits_and_vertices() {
+        let old = PlaneData::default();
+        let mut new = PlaneData::default();
+        new.levers.pilot = 102.0;
+        new.vertices[2] = [650.0, 180.0];
+        new.limits.push(WeightLimit {
+            name: "bagage".to_string(),
+            stations: vec![Kind::Bagage],
+            max_weight: 50.0,
+        });
+        let changes = diff_planes(&old, &new)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            changes,
+            vec![
+                "levers.pilot: 0.0 -> 102.0",
+                r#"limits[0]: added {"max_weight":50.0,"name":"bagage","stations":["bagage"]}"#,
+                "vertices[2][0]: 0.0 -> 650.0",
+                "vertices[2][1]: 0.0 -> 180.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_same_plane() {
+        assert!(diff_planes(&PlaneData::default(), &PlaneData::default()).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calc_wb::WeightAndBalance,
+    planes::{PlaneData, PlaneProperties},
+    Kind, WeightLever,
+};
+
+pub(crate) const SEATS: [Kind; 4] = [
+    Kind::Pilot,
+    Kind::CoPilot,
+    Kind::PaxLeftBack,
+    Kind::PaxRightBack,
+];
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Person {
+    pub name: String,
+    pub weight: f64,
+    /// Seat the person has to sit in, e.g. the pilot in command.
+    pub seat: Option<Kind>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SeatAssignment {
+    pub seats: BTreeMap<Kind, String>,
+    /// Smallest lever margin to the envelope, at take-off or landing. The
+    /// lever margin is used as the distance mixes weight and lever units.
+    pub margin: f64,
+}
+
+/// Finds all seat assignments of `people` that pass W&B, best margin first.
+/// `prop` holds the rest of the loading; the weights of its seats are replaced.
+/// The pilot seat is always occupied.
+pub fn assign_seats(
+    plane: &PlaneData,
+    prop: &PlaneProperties,
+    people: &[Person],
+) -> Vec<SeatAssignment> {
+    let levers = plane.to_lever_map();
+    let seats = SEATS
+        .into_iter()
+        .filter(|seat| levers.contains_key(seat))
+        .collect::<Vec<Kind>>();
+
+    let mut candidates = Vec::new();
+    permute(people, &seats, &mut Vec::new(), &mut candidates);
+
+    let mut assignments = candidates
+        .into_iter()
+        .filter(|assigned| assigned.contains(&Kind::Pilot))
+        .filter_map(|assigned| {
+            let mut loaded = prop.clone();
+            for seat in &seats {
+                loaded.insert(*seat, WeightLever::new(0.0, levers[seat]));
+            }
+            for (person, seat) in people.iter().zip(&assigned) {
+                loaded.insert(*seat, WeightLever::new(person.weight, levers[seat]));
+            }
+
+            plane.is_weight_and_balance_ok(&loaded).ok()?;
+            let mut margin = lever_margin(plane, plane.calc_weight_and_balance(&loaded));
+            if loaded.get(&Kind::TripFuel).is_some() {
+                plane.is_landing_weight_and_balance_ok(&loaded).ok()?;
+                margin = margin.min(lever_margin(
+                    plane,
+                    plane.calc_landing_weight_and_balance(&loaded),
+                ));
+            }
+
+            Some(SeatAssignment {
+                seats: people
+                    .iter()
+                    .zip(&assigned)
+                    .map(|(person, seat)| (*seat, person.name.clone()))
+                    .collect(),
+                margin,
+            })
+        })
+        .collect::<Vec<SeatAssignment>>();
+
+    assignments.sort_by(|a, b| b.margin.total_cmp(&a.margin));
+    assignments
+}
+
+/// Points that pass W&B are inside the envelope's weight range and so have a
+/// lever margin.
+fn lever_margin(plane: &PlaneData, point: WeightLever) -> f64 {
+    plane
+        .envelope_margin(point)
+        .ok()
+        .and_then(|margin| margin.lever)
+        .unwrap_or(0.0)
+}
+
+fn permute(people: &[Person], seats: &[Kind], assigned: &mut Vec<Kind>, out: &mut Vec<Vec<Kind>>) {
+    let Some(person) = people.get(assigned.len()) else {
+        out.push(assigned.clone());
+        return;
+    };
+    for seat in seats {
+        if assigned.contains(seat) || person.seat.is_some_and(|required| required != *seat) {
+            continue;
+        }
+        assigned.push(*seat);
+        permute(people, seats, assigned, out);
+        assigned.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn four_seater() -> PlaneData {
+        let mut plane = PlaneData::default();
+        plane.levers.pilot = 10.0;
+        plane.levers.co_pilot = 10.0;
+        plane.levers.passenger_left = Some(30.0);
+        plane.levers.passenger_right = Some(30.0);
+        plane.max_weights.max_take_off_weight = 1000.0;
+        plane.vertices = [
+            [0.0, 10.0],
+            [500.0, 10.0],
+            [1000.0, 10.0],
+            [1000.0, 25.0],
+            [500.0, 25.0],
+            [0.0, 25.0],
+        ];
+        plane
+    }
+
+    fn person(name: &str, weight: f64, seat: Option<Kind>) -> Person {
+        Person {
+            name: name.to_string(),
+            weight,
+            seat,
+        }
+    }
+
+    #[test]
+    fn assign_seats_honours_constraint() {
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(500.0, 20.0))]));
+        let people = [
+            person("anna", 60.0, Some(Kind::Pilot)),
+            person("bertil", 90.0, None),
+        ];
+        let assignments = assign_seats(&four_seater(), &prop, &people);
+
+        assert_eq!(assignments.len(), 3);
+        assert!(assignments
+            .iter()
+            .all(|assignment| assignment.seats[&Kind::Pilot] == "anna"));
+    }
+
+    #[test]
+    fn assign_seats_ranks_by_margin() {
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(500.0, 20.0))]));
+        let people = [
+            person("anna", 60.0, Some(Kind::Pilot)),
+            person("bertil", 100.0, None),
+        ];
+        let assignments = assign_seats(&four_seater(), &prop, &people);
+
+        assert_eq!(assignments[0].seats[&Kind::CoPilot], "bertil");
+        assert!(assignments[0].margin >= assignments[1].margin);
+    }
+
+    #[test]
+    fn assign_seats_margin_in_lever() {
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(895.0, 20.0))]));
+        let people = [person("anna", 100.0, Some(Kind::Pilot))];
+        let assignments = assign_seats(&four_seater(), &prop, &people);
+
+        // 995 kg is 5 kg below the top edge, the CG at 18900 / 995 is about
+        // 6.0 from the aft limit at 25.
+        assert_eq!(assignments.len(), 1);
+        assert!((assignments[0].margin - (25.0 - 18900.0 / 995.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn assign_seats_too_many_people() {
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(500.0, 20.0))]));
+        let people = (0..5)
+            .map(|i| person(&i.to_string(), 70.0, None))
+            .collect::<Vec<Person>>();
+
+        assert!(assign_seats(&four_seater(), &prop, &people).is_empty());
+    }
+}
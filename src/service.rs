@@ -0,0 +1,153 @@
+use log::debug;
+
+use crate::{
+    history::Outcome,
+    parse_footprints_from_input, parse_name_from_input, parse_values_from_input,
+    planes::{Input, ParsedInput, PlaneData, PlaneProperties},
+    profiles::{read_profiles, resolve_profiles, Profiles},
+    seating::{assign_seats, SeatAssignment, SEATS},
+    solver::max_fuel,
+    standard_masses::{builtin_mass_tables, read_mass_tables, resolve_standard_masses, MassTable},
+    stations::{fill_stations, resolve_litres, MANDATORY_STATIONS},
+    Kind,
+};
+
+/// What loadings refer to besides the planes, read once when a server starts.
+#[derive(Debug)]
+pub struct Lookups {
+    pub profiles: Profiles,
+    pub mass_tables: Vec<MassTable>,
+}
+
+impl Default for Lookups {
+    /// No profiles and the built-in mass tables.
+    fn default() -> Self {
+        Lookups {
+            profiles: Profiles::default(),
+            mass_tables: builtin_mass_tables(),
+        }
+    }
+}
+
+impl Lookups {
+    pub fn read(profiles: &str, mass_tables: &str) -> Lookups {
+        Lookups {
+            profiles: read_profiles(profiles),
+            mass_tables: read_mass_tables(mass_tables),
+        }
+    }
+}
+
+/// Finds the plane of the input in `planes` and resolves litres, profiles and
+/// standard masses. Stations that are not given use the default loading, see
+/// `fill_stations`. The `solved` ones, which the caller finds the weight of,
+/// are empty if they have neither.
+pub fn load<'a>(
+    input: &mut Input,
+    planes: &'a [PlaneData],
+    lookups: &Lookups,
+    solved: &[Kind],
+) -> Result<(&'a PlaneData, PlaneProperties), String> {
+    debug!("Got data: {:?}", input);
+    let name = parse_name_from_input(input);
+    let plane_config = planes
+        .iter()
+        .find(|plane| plane.name == name)
+        .ok_or_else(|| format!("Plane missing in config: {}", name))?;
+    resolve_litres(input, plane_config)?;
+    resolve_profiles(input, &lookups.profiles)?;
+    let mass_table = resolve_standard_masses(input, &lookups.mass_tables)?;
+    let mut values = parse_values_from_input(input)?;
+    let levers = plane_config.to_lever_map();
+    for kind in solved {
+        if levers.contains_key(kind) && !plane_config.default_loading.contains_key(kind) {
+            values.entry(*kind).or_insert(0.0);
+        }
+    }
+
+    let parsed_input = ParsedInput {
+        name,
+        values: fill_stations(plane_config, values)?,
+        footprints: parse_footprints_from_input(input)?,
+        people: input.people.clone(),
+        mass_table,
+    };
+    let plane_properties =
+        plane_config.properties(&parsed_input.values, parsed_input.footprints)?;
+
+    Ok((plane_config, plane_properties))
+}
+
+/// W&B of the input.
+pub fn calc(mut input: Input, planes: &[PlaneData], lookups: &Lookups) -> Result<Outcome, String> {
+    let (plane_config, plane_properties) = load(&mut input, planes, lookups, &[])?;
+    Ok(Outcome::calc(plane_config, &plane_properties))
+}
+
+/// W&B of the input like `calc`, `None` while a station in
+/// `MANDATORY_STATIONS` has no weight yet. Other errors are still errors.
+pub fn calc_partial(
+    input: Input,
+    planes: &[PlaneData],
+    lookups: &Lookups,
+) -> Result<Option<Outcome>, String> {
+    match calc(input.clone(), planes, lookups) {
+        Ok(outcome) => Ok(Some(outcome)),
+        Err(e) => {
+            let mut input = input;
+            load(&mut input, planes, lookups, &MANDATORY_STATIONS).map_err(|_| e)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Seatings of the people in the input that pass W&B, best margin first.
+pub fn calc_seats(
+    mut input: Input,
+    planes: &[PlaneData],
+    lookups: &Lookups,
+) -> Result<Vec<SeatAssignment>, String> {
+    let (plane_config, plane_properties) = load(&mut input, planes, lookups, &SEATS)?;
+    Ok(assign_seats(plane_config, &plane_properties, &input.people))
+}
+
+/// Most fuel in kg the rest of the input's loading can take, see `max_fuel`.
+pub fn calc_max_fuel(
+    mut input: Input,
+    planes: &[PlaneData],
+    lookups: &Lookups,
+) -> Result<Option<f64>, String> {
+    let (plane_config, plane_properties) = load(&mut input, planes, lookups, &[Kind::Fuel])?;
+    Ok(max_fuel(plane_config, &plane_properties))
+}
+
+/// Waits for Ctrl-C or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let interrupt = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("Could not listen for SIGTERM");
+        tokio::select! {
+            _ = interrupt => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    interrupt.await.expect("Could not listen for Ctrl-C");
+}
+
+/// Runs a calculation on its own thread, so it does not hold up other
+/// requests. Invalid input is an error, a panic is a bug and is passed on.
+pub async fn blocking<T: Send + 'static>(
+    calculation: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    match tokio::task::spawn_blocking(calculation).await {
+        Ok(result) => result,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(e) => Err(format!("Calculation cancelled: {}", e)),
+        },
+    }
+}
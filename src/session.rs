@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{history::Outcome, planes::Input};
+
+/// Change sent by a participant of a planning session, one JSON object per
+/// WebSocket text message, e.g. `{"type":"stations","values":{"pilot":82}}`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum SessionRequest {
+    /// Plans another plane, all stations start from its default loading.
+    #[serde(rename = "plane")]
+    Plane { name: String },
+    /// Sets stations, with values like in an input file. `null` returns a
+    /// station to the plane's default loading.
+    #[serde(rename = "stations")]
+    Stations { values: HashMap<String, Value> },
+}
+
+/// Sent by the server: the state to every participant after each change and
+/// when someone joins or leaves, an error only to who sent the change.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum SessionUpdate {
+    #[serde(rename = "state")]
+    State {
+        /// Number of changes made to the session.
+        revision: u64,
+        participants: usize,
+        input: Box<Input>,
+        /// W&B of the loading, `None` until a plane is chosen and its base,
+        /// pilot and fuel have a weight.
+        outcome: Option<Outcome>,
+    },
+    /// The change was not applied.
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+/// The loading everyone in a session looks at.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub revision: u64,
+    pub input: Input,
+    pub outcome: Option<Outcome>,
+}
+
+impl Session {
+    /// The loading with the change applied. The session itself is only
+    /// changed by `commit`, once the loading could be calculated or only
+    /// misses mandatory stations.
+    pub fn change(&self, request: SessionRequest) -> Result<Input, String> {
+        match request {
+            SessionRequest::Plane { name } => Ok(Input {
+                name,
+                ..Default::default()
+            }),
+            SessionRequest::Stations { .. } if self.input.name.is_empty() => {
+                Err("No plane chosen for the session".to_string())
+            }
+            SessionRequest::Stations { values } => {
+                let mut input = self.input.clone();
+                for (station, value) in values {
+                    if value.is_null() {
+                        input.values.remove(&station);
+                    } else {
+                        input.values.insert(station, value);
+                    }
+                }
+                Ok(input)
+            }
+        }
+    }
+
+    pub fn commit(&mut self, input: Input, outcome: Option<Outcome>) {
+        self.revision += 1;
+        self.input = input;
+        self.outcome = outcome;
+    }
+
+    pub fn state(&self, participants: usize) -> SessionUpdate {
+        SessionUpdate::State {
+            revision: self.revision,
+            participants,
+            input: Box::new(self.input.clone()),
+            outcome: self.outcome.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn stations_change_a_copy() {
+        let mut session = Session::default();
+        let stations = || {
+            serde_json::from_value::<SessionRequest>(
+                json!({"type": "stations", "values": {"pilot": 82, "fuel": "60L", "co_pilot": null}}),
+            )
+            .unwrap()
+        };
+
+        assert!(session.change(stations()).is_err());
+        session.input = session
+            .change(SessionRequest::Plane {
+                name: "SEMOA".to_string(),
+            })
+            .unwrap();
+        session
+            .input
+            .values
+            .insert("co_pilot".to_string(), json!(75));
+        let input = session.change(stations()).unwrap();
+
+        assert_eq!(input.name, "SEMOA");
+        assert_eq!(input.values["pilot"], json!(82));
+        assert_eq!(input.values["fuel"], json!("60L"));
+        assert!(!input.values.contains_key("co_pilot"));
+        assert_eq!(session.input.values.len(), 1);
+        assert_eq!(session.revision, 0);
+    }
+}
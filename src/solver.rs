@@ -0,0 +1,211 @@
+use crate::{
+    calc_wb::WeightAndBalance,
+    planes::{PlaneData, PlaneProperties},
+    Kind, WeightLever,
+};
+
+const BALLAST_STEP: f64 = 0.5;
+/// Stations that can take ballast, seats are for people.
+const BALLAST_STATIONS: [Kind; 6] = [
+    Kind::Bagage,
+    Kind::BagageFront,
+    Kind::BagageBack,
+    Kind::BagageWings,
+    Kind::WaterBallast,
+    Kind::TailBallast,
+];
+const FUEL_STEP: f64 = 0.5;
+
+#[derive(Debug, PartialEq)]
+pub struct Ballast {
+    pub station: Kind,
+    pub weight: f64,
+}
+
+/// Minimum trip fuel (kg) that has to be burnt before the plane is below its
+/// maximum landing weight. Returns `None` if the plane has no landing limit
+/// or if burning all the fuel on board still leaves it above the limit.
+pub fn min_trip_fuel_for_landing(plane: &PlaneData, prop: &PlaneProperties) -> Option<f64> {
+    let max_landing_weight = plane.max_weights.max_landing_weight?;
+    let fuel = prop.get(&Kind::Fuel).map(|wb| wb.weight).unwrap_or(0.0);
+    let trip_fuel = (prop.get_total_weights() - max_landing_weight).max(0.0);
+    (trip_fuel <= fuel).then_some(trip_fuel)
+}
+
+/// Smallest ballast, in steps of 0.5 kg, that makes the W&B ok when put at one
+/// of the plane's baggage or ballast stations. Returns `None` if no single station can take
+/// enough ballast without breaking some other limit.
+pub fn ballast_for_cg(plane: &PlaneData, prop: &PlaneProperties) -> Option<Ballast> {
+    let max_ballast = plane.max_weights.max_take_off_weight - prop.get_total_weights();
+    if max_ballast < 0.0 {
+        return None;
+    }
+    let steps = (max_ballast / BALLAST_STEP).floor() as u32;
+
+    let mut stations = plane
+        .to_lever_map()
+        .into_iter()
+        .filter(|(kind, _)| BALLAST_STATIONS.contains(kind))
+        .collect::<Vec<(Kind, f64)>>();
+    stations.sort_by_key(|(kind, _)| *kind);
+
+    stations
+        .into_iter()
+        .filter_map(|(station, lever)| {
+            let current = prop.get(&station).map(|wb| wb.weight).unwrap_or(0.0);
+            (0..=steps)
+                .map(|step| step as f64 * BALLAST_STEP)
+                .find(|weight| {
+                    let mut ballasted = prop.clone();
+                    ballasted.insert(station, WeightLever::new(current + weight, lever));
+                    plane.is_weight_and_balance_ok(&ballasted).is_ok()
+                })
+                .map(|weight| Ballast { station, weight })
+        })
+        .min_by(|a, b| a.weight.total_cmp(&b.weight))
+}
+
+/// Most fuel, in steps of 0.5 kg up to the max fuel weight, with which the
+/// take-off and landing W&B are ok for the rest of the loading. Returns
+/// `None` if no amount of fuel makes the W&B ok or the plane has no fuel.
+pub fn max_fuel(plane: &PlaneData, prop: &PlaneProperties) -> Option<f64> {
+    let lever = plane.levers.fuel?;
+    let steps = (plane.max_weights.max_fuel_weight / FUEL_STEP).floor() as u32;
+    (0..=steps)
+        .rev()
+        .map(|step| step as f64 * FUEL_STEP)
+        .find(|fuel| {
+            let mut fueled = prop.clone();
+            fueled.insert(Kind::Fuel, WeightLever::new(*fuel, lever));
+            plane.is_weight_and_balance_ok(&fueled).is_ok()
+                && plane.is_landing_weight_and_balance_ok(&fueled).is_ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn min_trip_fuel_above_mlw() {
+        let mut plane = PlaneData::default();
+        plane.max_weights.max_landing_weight = Some(10.0);
+        let prop = PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(9.0, 1.0)),
+            (Kind::Fuel, WeightLever::new(5.0, 1.0)),
+        ]));
+
+        assert_eq!(min_trip_fuel_for_landing(&plane, &prop), Some(4.0));
+    }
+
+    #[test]
+    fn min_trip_fuel_below_mlw() {
+        let mut plane = PlaneData::default();
+        plane.max_weights.max_landing_weight = Some(20.0);
+        let prop = PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(9.0, 1.0)),
+            (Kind::Fuel, WeightLever::new(5.0, 1.0)),
+        ]));
+
+        assert_eq!(min_trip_fuel_for_landing(&plane, &prop), Some(0.0));
+    }
+
+    #[test]
+    fn ballast_aft_station() {
+        let mut plane = PlaneData::default();
+        plane.levers.bagage = Some(40.0);
+        plane.max_weights.max_take_off_weight = 1000.0;
+        plane.vertices = [
+            [0.0, 10.0],
+            [500.0, 10.0],
+            [1000.0, 10.0],
+            [1000.0, 20.0],
+            [500.0, 20.0],
+            [0.0, 20.0],
+        ];
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(100.0, 5.0))]));
+
+        assert_eq!(
+            ballast_for_cg(&plane, &prop),
+            Some(Ballast {
+                station: Kind::Bagage,
+                weight: 17.0
+            })
+        );
+    }
+
+    #[test]
+    fn ballast_not_in_seats() {
+        let mut plane = PlaneData::default();
+        plane.levers.pilot = 40.0;
+        plane.levers.co_pilot = 40.0;
+        plane.max_weights.max_take_off_weight = 1000.0;
+        plane.vertices = [
+            [0.0, 10.0],
+            [500.0, 10.0],
+            [1000.0, 10.0],
+            [1000.0, 20.0],
+            [500.0, 20.0],
+            [0.0, 20.0],
+        ];
+        let prop =
+            PlaneProperties::new(HashMap::from([(Kind::Base, WeightLever::new(100.0, 5.0))]));
+
+        assert_eq!(ballast_for_cg(&plane, &prop), None);
+        plane.levers.tail_ballast = Some(40.0);
+        assert_eq!(
+            ballast_for_cg(&plane, &prop),
+            Some(Ballast {
+                station: Kind::TailBallast,
+                weight: 17.0
+            })
+        );
+    }
+
+    #[test]
+    fn max_fuel_up_to_mtow() {
+        let mut plane = PlaneData::default();
+        plane.levers.fuel = Some(10.0);
+        plane.max_weights.max_take_off_weight = 150.0;
+        plane.max_weights.max_fuel_weight = 80.0;
+        plane.vertices = [
+            [0.0, 0.0],
+            [500.0, 0.0],
+            [1000.0, 0.0],
+            [1000.0, 20.0],
+            [500.0, 20.0],
+            [0.0, 20.0],
+        ];
+        let prop = PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(99.8, 5.0)),
+            (Kind::TripFuel, WeightLever::new(0.0, 10.0)),
+        ]));
+
+        assert_eq!(max_fuel(&plane, &prop), Some(50.0));
+        plane.max_weights.max_take_off_weight = 90.0;
+        assert_eq!(max_fuel(&plane, &prop), None);
+    }
+
+    #[test]
+    fn min_trip_fuel_more_than_on_board() {
+        let mut plane = PlaneData::default();
+        plane.max_weights.max_landing_weight = Some(10.0);
+        let prop = PlaneProperties::new(HashMap::from([
+            (Kind::Base, WeightLever::new(12.0, 1.0)),
+            (Kind::Fuel, WeightLever::new(1.0, 1.0)),
+        ]));
+
+        assert_eq!(min_trip_fuel_for_landing(&plane, &prop), None);
+    }
+
+    #[test]
+    fn min_trip_fuel_no_mlw() {
+        let plane = PlaneData::default();
+        let prop = PlaneProperties::default();
+
+        assert_eq!(min_trip_fuel_for_landing(&plane, &prop), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, fs::File, io::BufReader, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{planes::Input, seating::SEATS, Kind};
+
+pub const DEFAULT_MASS_TABLE: &str = "easa";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    #[serde(rename = "adult")]
+    Adult,
+    #[serde(rename = "adult_male")]
+    AdultMale,
+    #[serde(rename = "adult_female")]
+    AdultFemale,
+    #[serde(rename = "child")]
+    Child,
+    #[serde(rename = "infant")]
+    Infant,
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adult" => Ok(Category::Adult),
+            "adult_male" => Ok(Category::AdultMale),
+            "adult_female" => Ok(Category::AdultFemale),
+            "child" => Ok(Category::Child),
+            "infant" => Ok(Category::Infant),
+            _ => Err(format!("Invalid passenger category: {}", s)),
+        }
+    }
+}
+
+/// Standard mass in kg per passenger category.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MassTable {
+    pub name: String,
+    pub masses: BTreeMap<Category, f64>,
+}
+
+impl MassTable {
+    /// Weight of a station value like `"adult_male"` or `"adult_female+infant"`.
+    pub fn resolve(&self, value: &str) -> Result<f64, String> {
+        value
+            .split('+')
+            .map(|category| {
+                let category = Category::from_str(category.trim())?;
+                self.masses.get(&category).copied().ok_or_else(|| {
+                    format!("Mass table {} has no mass for {:?}", self.name, category)
+                })
+            })
+            .sum()
+    }
+}
+
+/// Built-in tables from EASA Air OPS CAT.POL.MAB.100. "easa" is table 1 for
+/// aircraft with 20 or more passenger seats and "easa_1_5" the column of
+/// table 2 for 1 to 5 seats. Infants on an adult's lap are part of the adult
+/// mass, an infant in a seat of its own should be entered as a child.
+pub fn builtin_mass_tables() -> Vec<MassTable> {
+    vec![
+        MassTable {
+            name: "easa".to_string(),
+            masses: BTreeMap::from([
+                (Category::Adult, 84.0),
+                (Category::AdultMale, 88.0),
+                (Category::AdultFemale, 70.0),
+                (Category::Child, 35.0),
+                (Category::Infant, 0.0),
+            ]),
+        },
+        MassTable {
+            name: "easa_1_5".to_string(),
+            masses: BTreeMap::from([
+                (Category::AdultMale, 104.0),
+                (Category::AdultFemale, 86.0),
+                (Category::Child, 35.0),
+                (Category::Infant, 0.0),
+            ]),
+        },
+    ]
+}
+
+/// Built-in tables followed by the custom tables in `path`, if it exists.
+pub fn read_mass_tables(path: &str) -> Vec<MassTable> {
+    let mut tables = builtin_mass_tables();
+    if let Ok(file) = File::open(path) {
+        let custom: Vec<MassTable> =
+            serde_json::from_reader(BufReader::new(file)).expect("Invalid format of mass tables");
+        tables.extend(custom);
+    }
+    tables
+}
+
+pub fn find_mass_table<'a>(tables: &'a [MassTable], name: &str) -> Result<&'a MassTable, String> {
+    tables
+        .iter()
+        .rev()
+        .find(|table| table.name == name)
+        .ok_or_else(|| format!("Unknown mass table: {}", name))
+}
+
+/// Replaces passenger categories among the input values with standard masses
+/// from the input's mass table, only seats take categories. Returns the name of the table if any standard
+/// mass was used.
+pub fn resolve_standard_masses(
+    input: &mut Input,
+    tables: &[MassTable],
+) -> Result<Option<String>, String> {
+    let table_name = input.mass_table.as_deref().unwrap_or(DEFAULT_MASS_TABLE);
+    let mut used = false;
+    for (key, value) in input.values.iter_mut() {
+        if let Some(categories) = value.as_str() {
+            let kind = Kind::from_str(key)?;
+            if !SEATS.contains(&kind) {
+                return Err(format!("Passenger categories are only for seats: {}", key));
+            }
+            let table = find_mass_table(tables, table_name)?;
+            *value = serde_json::Value::from(table.resolve(categories)?);
+            used = true;
+        }
+    }
+    Ok(used.then(|| table_name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_categories() {
+        let tables = builtin_mass_tables();
+        let table = find_mass_table(&tables, "easa").unwrap();
+
+        assert_eq!(table.resolve("adult_male"), Ok(88.0));
+        assert_eq!(table.resolve("adult_female + child"), Ok(105.0));
+        assert!(table.resolve("pilot").is_err());
+    }
+
+    #[test]
+    fn custom_table_overrides_builtin() {
+        let mut tables = builtin_mass_tables();
+        tables.push(MassTable {
+            name: "easa".to_string(),
+            masses: BTreeMap::from([(Category::Adult, 90.0)]),
+        });
+
+        assert_eq!(
+            find_mass_table(&tables, "easa").unwrap().resolve("adult"),
+            Ok(90.0)
+        );
+    }
+
+    #[test]
+    fn resolve_input() {
+        let mut input = Input {
+            mass_table: Some("easa_1_5".to_string()),
+            ..Default::default()
+        };
+        input.values.insert(
+            "passenger_left".to_string(),
+            serde_json::Value::from("adult_male"),
+        );
+        input
+            .values
+            .insert("pilot".to_string(), serde_json::Value::from(70.0));
+
+        assert_eq!(
+            resolve_standard_masses(&mut input, &builtin_mass_tables()),
+            Ok(Some("easa_1_5".to_string()))
+        );
+        assert_eq!(input.values["passenger_left"].as_f64(), Some(104.0));
+    }
+
+    #[test]
+    fn categories_only_on_seats() {
+        let mut input = Input::default();
+        input
+            .values
+            .insert("base".to_string(), serde_json::Value::from("adult"));
+
+        assert_eq!(
+            resolve_standard_masses(&mut input, &builtin_mass_tables()),
+            Err("Passenger categories are only for seats: base".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_input_without_categories() {
+        let mut input = Input::default();
+        input
+            .values
+            .insert("pilot".to_string(), serde_json::Value::from(70.0));
+
+        assert_eq!(
+            resolve_standard_masses(&mut input, &builtin_mass_tables()),
+            Ok(None)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    planes::{Input, PlaneData, DEFAULT_FUEL_DENSITY},
+    Kind,
+};
+
+/// Parses station flags like `--pilot 82 --fuel 60L --bagage-back=5` into an
+/// input for the plane `name`. Weights in kg become numbers. Litres with an
+/// `L` suffix for fuel and water ballast, saved profiles like `@anna` and
+/// passenger categories like `adult_male` are kept as text for
+/// `resolve_litres`, `resolve_profiles` and `resolve_standard_masses`.
+pub fn parse_station_flags(name: &str, args: &[String]) -> Result<Input, String> {
+    let mut input = Input {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let flag = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("Expected a station flag, got: {}", arg))?;
+        let (station, value) = match flag.split_once('=') {
+            Some((station, value)) => (station, value.to_string()),
+            None => (
+                flag,
+                args.next()
+                    .ok_or_else(|| format!("Missing weight for station: {}", flag))?
+                    .clone(),
+            ),
+        };
+        let kind = Kind::from_str(&station.replace('-', "_"))
+            .map_err(|_| format!("Unknown station: {}", station))?;
+        let value = if value.starts_with(|c: char| c == '@' || c.is_ascii_alphabetic())
+            || litres(&value).is_some()
+        {
+            serde_json::Value::from(value)
+        } else {
+            let kg = value.strip_suffix("kg").unwrap_or(&value);
+            let kg = kg
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid weight: {}", value))?;
+            if kg < 0.0 {
+                return Err(format!("Weight must not be negative: {}", value));
+            }
+            serde_json::Value::from(kg)
+        };
+        input.values.insert(station_key(kind), value);
+    }
+
+    Ok(input)
+}
+
+/// Parses station flags like `parse_station_flags` for a plane. Only stations
+/// with a lever on the plane are accepted and litres are converted to kg.
+pub fn parse_station_args(plane: &PlaneData, args: &[String]) -> Result<Input, String> {
+    let mut input = parse_station_flags(&plane.name, args)?;
+    let levers = plane.to_lever_map();
+    for key in input.values.keys() {
+        let kind = Kind::from_str(key)?;
+        if !levers.contains_key(&kind) {
+            let mut stations = levers
+                .keys()
+                .map(|kind| station_flag(*kind))
+                .collect::<Vec<String>>();
+            stations.sort();
+            return Err(format!(
+                "{} has no station {}, expected one of: {}",
+                plane.name,
+                station_flag(kind),
+                stations.join(", ")
+            ));
+        }
+    }
+    resolve_litres(&mut input, plane)?;
+
+    Ok(input)
+}
+
+/// Replaces values in litres like `"60L"` among the input values with kg. Fuel
+/// uses the plane's fuel density and water ballast 1 kg/L.
+pub fn resolve_litres(input: &mut Input, plane: &PlaneData) -> Result<(), String> {
+    for (key, value) in input.values.iter_mut() {
+        let Some(litres) = value.as_str().and_then(litres) else {
+            continue;
+        };
+        if litres < 0.0 {
+            return Err(format!("Litres must not be negative: {}", value));
+        }
+        let density = match Kind::from_str(key)? {
+            Kind::Fuel | Kind::TripFuel => plane.fuel_density.unwrap_or(DEFAULT_FUEL_DENSITY),
+            Kind::WaterBallast | Kind::TailBallast => 1.0,
+            _ => {
+                return Err(format!(
+                    "Litres are only allowed for fuel and ballast: {}",
+                    value
+                ))
+            }
+        };
+        *value = serde_json::Value::from(litres * density);
+    }
+    Ok(())
+}
+
+/// Stations that need a weight, given or from the default loading, on planes
+/// that have them. Other stations are empty if not given.
+pub const MANDATORY_STATIONS: [Kind; 3] = [Kind::Base, Kind::Pilot, Kind::Fuel];
+
+/// Loading of all stations of the plane, taken from `weights`, then from the
+/// plane's default loading and otherwise empty. The weights must be for
+/// stations of the plane and not negative, and `MANDATORY_STATIONS` must have
+/// a weight.
+pub fn fill_stations(
+    plane: &PlaneData,
+    weights: HashMap<Kind, f64>,
+) -> Result<HashMap<Kind, f64>, String> {
+    let levers = plane.to_lever_map();
+    for (kind, weight) in &weights {
+        if !levers.contains_key(kind) {
+            return Err(format!(
+                "{} has no station {}",
+                plane.name,
+                station_key(*kind)
+            ));
+        }
+        if *weight < 0.0 {
+            return Err(format!(
+                "Weight of {} must not be negative: {}",
+                station_key(*kind),
+                weight
+            ));
+        }
+    }
+    for kind in MANDATORY_STATIONS {
+        if levers.contains_key(&kind)
+            && !weights.contains_key(&kind)
+            && !plane.default_loading.contains_key(&kind)
+        {
+            return Err(format!("Missing weight for station: {}", station_key(kind)));
+        }
+    }
+    Ok(levers
+        .into_keys()
+        .map(|kind| {
+            let weight = weights
+                .get(&kind)
+                .or_else(|| plane.default_loading.get(&kind))
+                .copied()
+                .unwrap_or(0.0);
+            (kind, weight)
+        })
+        .collect())
+}
+
+fn litres(value: &str) -> Option<f64> {
+    value
+        .strip_suffix(['L', 'l'])
+        .and_then(|litres| litres.trim().parse::<f64>().ok())
+}
+
+/// Name of the station in input files, e.g. `co_pilot`.
+pub fn station_key(kind: Kind) -> String {
+    serde_json::to_value(kind)
+        .expect("Kind is serializable")
+        .as_str()
+        .expect("Kind is a string")
+        .to_string()
+}
+
+fn station_flag(kind: Kind) -> String {
+    format!("--{}", station_key(kind).replace('_', "-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_kg_and_litres() {
+        let mut plane = PlaneData::default();
+        plane.levers.fuel = Some(180.0);
+        plane.levers.bagage_back = Some(280.0);
+        let input = parse_station_args(
+            &plane,
+            &args(&["--pilot", "82", "--fuel", "50L", "--bagage-back=5kg"]),
+        )
+        .unwrap();
+
+        assert_eq!(input.values["pilot"].as_f64(), Some(82.0));
+        assert_eq!(
+            input.values["fuel"].as_f64(),
+            Some(50.0 * DEFAULT_FUEL_DENSITY)
+        );
+        assert_eq!(input.values["bagage_back"].as_f64(), Some(5.0));
+    }
+
+    #[test]
+    fn keep_profiles_and_categories() {
+        let plane = PlaneData::default();
+        let input = parse_station_args(
+            &plane,
+            &args(&["--pilot", "@anna", "--co-pilot=adult_male"]),
+        )
+        .unwrap();
+
+        assert_eq!(input.values["pilot"].as_str(), Some("@anna"));
+        assert_eq!(input.values["co_pilot"].as_str(), Some("adult_male"));
+    }
+
+    #[test]
+    fn litres_resolved_on_plane() {
+        let plane = PlaneData {
+            fuel_density: Some(0.8),
+            ..Default::default()
+        };
+        let mut input =
+            parse_station_flags("SEMOA", &args(&["--fuel", "50L", "--pilot", "@paul"])).unwrap();
+
+        assert_eq!(input.values["fuel"].as_str(), Some("50L"));
+        resolve_litres(&mut input, &plane).unwrap();
+        assert_eq!(input.values["fuel"].as_f64(), Some(40.0));
+        assert_eq!(input.values["pilot"].as_str(), Some("@paul"));
+    }
+
+    #[test]
+    fn parse_station_missing_on_plane() {
+        let plane = PlaneData::default();
+
+        assert!(parse_station_args(&plane, &args(&["--passenger-left", "70"])).is_err());
+    }
+
+    #[test]
+    fn parse_negative() {
+        let mut plane = PlaneData::default();
+        plane.levers.fuel = Some(180.0);
+
+        assert_eq!(
+            parse_station_args(&plane, &args(&["--pilot", "-80"])).unwrap_err(),
+            "Weight must not be negative: -80"
+        );
+        assert!(parse_station_args(&plane, &args(&["--pilot=-80kg"])).is_err());
+        assert!(parse_station_args(&plane, &args(&["--fuel", "-20L"])).is_err());
+    }
+
+    #[test]
+    fn parse_litres_not_fuel() {
+        let plane = PlaneData::default();
+
+        assert!(parse_station_args(&plane, &args(&["--pilot", "70L"])).is_err());
+    }
+
+    #[test]
+    fn fill_from_defaults() {
+        let mut plane = PlaneData::default();
+        plane.default_loading.insert(Kind::Base, 450.0);
+        plane.default_loading.insert(Kind::Pilot, 80.0);
+        let weights = fill_stations(&plane, HashMap::from([(Kind::Pilot, 70.0)])).unwrap();
+
+        assert_eq!(weights[&Kind::Base], 450.0);
+        assert_eq!(weights[&Kind::Pilot], 70.0);
+        assert_eq!(weights[&Kind::CoPilot], 0.0);
+    }
+
+    #[test]
+    fn fill_rejects_other_stations() {
+        let plane = PlaneData {
+            name: "SEMOA".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            fill_stations(&plane, HashMap::from([(Kind::BagageWings, 5.0)])),
+            Err("SEMOA has no station bagage_wings".to_string())
+        );
+        assert_eq!(
+            fill_stations(&plane, HashMap::from([(Kind::Pilot, -70.0)])),
+            Err("Weight of pilot must not be negative: -70".to_string())
+        );
+    }
+
+    #[test]
+    fn fill_requires_mandatory_stations() {
+        let mut plane = PlaneData::default();
+        plane.levers.fuel = Some(180.0);
+        plane.default_loading.insert(Kind::Base, 450.0);
+
+        assert_eq!(
+            fill_stations(&plane, HashMap::from([(Kind::Fuel, 40.0)])),
+            Err("Missing weight for station: pilot".to_string())
+        );
+        assert_eq!(
+            fill_stations(&plane, HashMap::from([(Kind::Pilot, 70.0)])),
+            Err("Missing weight for station: fuel".to_string())
+        );
+        let weights = fill_stations(
+            &plane,
+            HashMap::from([(Kind::Pilot, 70.0), (Kind::Fuel, 40.0)]),
+        )
+        .unwrap();
+        assert_eq!(weights[&Kind::Base], 450.0);
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio_rustls::{
+    rustls::{
+        crypto::ring::default_provider,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+        ClientConfig, RootCertStore, ServerConfig,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+/// Certificate chain and private key of the server, PEM files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: String,
+    pub key: String,
+}
+
+impl TlsFiles {
+    pub fn acceptor(&self) -> Result<TlsAcceptor, String> {
+        let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_no_client_auth()
+            .with_single_cert(read_certs(&self.cert)?, read_key(&self.key)?)
+            .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Connector that trusts the certificates in the PEM file `ca`, e.g. the
+/// server's own self-signed certificate.
+pub fn connector(ca: &str) -> Result<TlsConnector, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in read_certs(ca)? {
+        roots
+            .add(cert)
+            .map_err(|e| format!("Invalid certificate in {}: {}", ca, e))?;
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Name the server's certificate must have, the host of an address like
+/// `wbl.local:8080` or `192.168.1.5:8080`.
+pub fn server_name(address: &str) -> Result<ServerName<'static>, String> {
+    let host = address
+        .rsplit_once(':')
+        .map_or(address, |(host, _)| host)
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    ServerName::try_from(host.to_string())
+        .map_err(|e| format!("Invalid server name {}: {}", host, e))
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("Certificate {}: {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate in {}", path));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &str) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(path).map_err(|e| format!("Key {}: {}", path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Invalid key {}: {}", path, e))?
+        .ok_or_else(|| format!("No private key in {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Self-signed certificate for `localhost` and 127.0.0.1 written to
+    /// temporary files, removed again when dropped.
+    struct SelfSigned(TlsFiles);
+
+    impl SelfSigned {
+        fn new(name: &str) -> SelfSigned {
+            let key = rcgen::generate_simple_self_signed(vec![
+                "localhost".to_string(),
+                "127.0.0.1".to_string(),
+            ])
+            .unwrap();
+            let dir = env::temp_dir();
+            let cert = dir.join(format!("wbl-{}-{}-cert.pem", name, std::process::id()));
+            let private = dir.join(format!("wbl-{}-{}-key.pem", name, std::process::id()));
+            fs::write(&cert, key.cert.pem()).unwrap();
+            fs::write(&private, key.key_pair.serialize_pem()).unwrap();
+            SelfSigned(TlsFiles {
+                cert: cert.to_str().unwrap().to_string(),
+                key: private.to_str().unwrap().to_string(),
+            })
+        }
+    }
+
+    impl Drop for SelfSigned {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0.cert);
+            let _ = fs::remove_file(&self.0.key);
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_with_self_signed() {
+        let files = SelfSigned::new("handshake");
+        let acceptor = files.0.acceptor().unwrap();
+        let connector = connector(&files.0.cert).unwrap();
+        let (client, server) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            let mut stream = acceptor.accept(server).await.unwrap();
+            stream.write_all(b"W&B is OK").await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+        let mut stream = connector
+            .connect(server_name("localhost:8080").unwrap(), client)
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await.unwrap();
+
+        assert_eq!(reply, "W&B is OK");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn untrusted_certificate() {
+        let files = SelfSigned::new("untrusted");
+        let other = SelfSigned::new("other");
+        let acceptor = files.0.acceptor().unwrap();
+        let connector = connector(&other.0.cert).unwrap();
+        let (client, server) = duplex(4096);
+
+        tokio::spawn(async move { acceptor.accept(server).await });
+
+        assert!(connector
+            .connect(server_name("127.0.0.1:8080").unwrap(), client)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn missing_files() {
+        let files = TlsFiles {
+            cert: "missing-cert.pem".to_string(),
+            key: "missing-key.pem".to_string(),
+        };
+
+        assert!(files.acceptor().is_err());
+        assert!(connector("missing-cert.pem").is_err());
+    }
+}